@@ -0,0 +1,82 @@
+pub mod monte_carlo;
+pub mod placement;
+pub mod profile;
+
+use std::{str::FromStr, fmt::Display, time::Duration};
+
+use super::{field::CheckField, shot::Shot, weapons::{Arsenal, Special}};
+use monte_carlo::{MonteCarloShooter, Budget};
+use placement::{Placer, RandomPlacer, SmartPlacer};
+
+/// Something that decides where the computer shoots next.
+pub trait Shooter {
+    fn choose_shot(&mut self, checked: &CheckField) -> Shot;
+    /// `shots` different cells to fire at together, for games with salvos.
+    fn choose_volley(&mut self, checked: &CheckField, shots: usize) -> Vec<Shot>;
+    /// One of the special attacks left in `arsenal`, or `None` when a
+    /// plain shot is the better move.
+    fn choose_special(&mut self, checked: &CheckField, arsenal: Arsenal) -> Option<Special>;
+}
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn budget(&self) -> Budget {
+        match self {
+            Difficulty::Easy => Budget::Samples(10),
+            Difficulty::Normal => Budget::Samples(1000),
+            Difficulty::Hard => Budget::Time(Duration::from_millis(300)),
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        match self {
+            Difficulty::Easy => 1,
+            _ => monte_carlo::default_threads(),
+        }
+    }
+
+    pub fn shooter(&self) -> MonteCarloShooter {
+        MonteCarloShooter::new(self.budget(), self.threads())
+    }
+
+    pub fn placer(&self) -> Box<dyn Placer> {
+        match self {
+            Difficulty::Easy => Box::new(RandomPlacer::new()),
+            Difficulty::Normal => Box::new(SmartPlacer::new(true, true, false)),
+            Difficulty::Hard => Box::new(SmartPlacer::new(true, true, true)),
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = ParseDifficultyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Difficulty::Easy),
+            "normal" => Ok(Difficulty::Normal),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(ParseDifficultyError::InvalidInput),
+        }
+    }
+}
+
+pub enum ParseDifficultyError {
+    InvalidInput,
+}
+
+impl Display for ParseDifficultyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseDifficultyError::InvalidInput => write!(f, "difficulty must be one of 'easy'/'normal'/'hard'"),
+        }
+    }
+}
@@ -0,0 +1,514 @@
+use std::{thread, time::{Duration, Instant}};
+
+use super::Shooter;
+use crate::battleships::{
+    bitboard::{BitPlayerField, BitSet},
+    field::{CheckField, CheckedCell, FieldCell},
+    game_constants::FLEET,
+    rules::TouchingPolicy,
+    shape::Shape,
+    ship::Ship,
+    shot::Shot,
+    weapons::{self, Arsenal, Special, Heading, Weapon},
+};
+use crate::utilities::random::Random;
+
+/// How many placement attempts a single ship gets before the whole
+/// sample is thrown away.
+const ATTEMPTS_PER_SHIP: usize = 100;
+/// How many ship cells a bomb must be expected to hit to be worth it.
+const BOMB_THRESHOLD: f64 = 1.0;
+/// How likely a torpedo must be to hit something to be worth it.
+const TORPEDO_THRESHOLD: f64 = 0.5;
+/// How uncertain the cells around a sonar ping must be, summed up, to be
+/// worth the turn. A cell that's a coin flip counts a quarter.
+const SONAR_THRESHOLD: f64 = 1.0;
+
+/// How much thinking the AI is allowed to do for every shot.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub enum Budget {
+    /// Stop after this many consistent fleet layouts were found.
+    Samples(usize),
+    /// Keep sampling until this much time has passed.
+    Time(Duration),
+}
+
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map(|count| count.get()).unwrap_or(1)
+}
+
+/// Samples complete enemy fleets that agree with everything on the
+/// `CheckField` and shoots at the cell that is occupied most often.
+pub struct MonteCarloShooter {
+    budget: Budget,
+    threads: usize,
+    fleet: Vec<Shape>,
+    prior: Option<Vec<f64>>,
+    prior_weight: f64,
+    random: Random,
+}
+
+impl MonteCarloShooter {
+    pub fn new(budget: Budget, threads: usize) -> MonteCarloShooter {
+        MonteCarloShooter {
+            budget,
+            threads: threads.max(1),
+            fleet: FLEET.to_vec(),
+            prior: None,
+            prior_weight: 0.0,
+            random: Random::new(),
+        }
+    }
+
+    /// The fleet the enemy plays with, the standard one unless told.
+    pub fn set_fleet(&mut self, fleet: &[Shape]) {
+        self.fleet = fleet.to_vec();
+    }
+
+    /// Makes cells with a high `prior` (scaled to `0.0..=1.0`) up to
+    /// `1.0 + weight` times more attractive than the sampling alone says.
+    pub fn set_prior(&mut self, prior: Vec<f64>, weight: f64) {
+        self.prior = Some(prior);
+        self.prior_weight = weight;
+    }
+
+    pub fn budget(&self) -> Budget {
+        self.budget
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// The prior and its weight, if one was set.
+    pub fn prior(&self) -> Option<(&[f64], f64)> {
+        self.prior.as_ref().map(|prior| (prior.as_slice(), self.prior_weight))
+    }
+
+    pub fn random(&self) -> &Random {
+        &self.random
+    }
+
+    /// Replaces the random generator, so a restored shooter makes the
+    /// same choices the saved one would have.
+    pub fn set_random(&mut self, random: Random) {
+        self.random = random;
+    }
+
+    /// The shot it would take, together with the heat map it was picked
+    /// from: how many sampled layouts put a ship on every cell. Cells that
+    /// are already known stay at zero.
+    pub fn analyse(&mut self, checked: &CheckField) -> (Shot, Vec<u32>) {
+        let knowledge = Knowledge::new(checked, &self.fleet);
+        let heat = self.sample_heat(&knowledge);
+        let candidates = knowledge.candidates();
+        let score = |index: usize| self.score(&heat, index);
+
+        let best = candidates.iter().map(|&index| score(index)).fold(0.0, f64::max);
+        let mut choices: Vec<usize> = if best > 0.0 {
+            candidates.into_iter().filter(|&index| score(index) == best).collect()
+        } else {
+            // Nothing consistent was found in time, so finish off wounded
+            // ships first and guess anywhere otherwise.
+            let near_hits = knowledge.next_to_hits(&candidates);
+            if near_hits.is_empty() { candidates } else { near_hits }
+        };
+
+        if choices.is_empty() {
+            choices = (0..knowledge.cells())
+                .filter(|&index| {
+                    let (x, y) = knowledge.cell(index);
+                    checked.at(x, y) == CheckedCell::Unchecked
+                })
+                .collect();
+        }
+
+        let index = choices[self.random.below(choices.len())];
+        let (x, y) = knowledge.cell(index);
+        (Shot { x, y }, heat)
+    }
+
+    /// How good a shot the cell at `index` is, the heat boosted by the prior.
+    fn score(&self, heat: &[u32], index: usize) -> f64 {
+        let prior = self.prior.as_ref().map_or(0.0, |prior| prior[index]);
+        heat[index] as f64 * (1.0 + self.prior_weight * prior)
+    }
+
+    fn sample_heat(&mut self, knowledge: &Knowledge) -> Vec<u32> {
+        let seeds: Vec<u64> = (0..self.threads).map(|_| self.random.next_u64()).collect();
+        let budget = self.budget;
+        let threads = self.threads;
+
+        let tallies: Vec<Vec<u32>> = thread::scope(|scope| {
+            let handles: Vec<_> = seeds.into_iter().enumerate().map(|(i, seed)| {
+                let budget = match budget {
+                    Budget::Samples(count) => {
+                        Budget::Samples(count / threads + usize::from(i < count % threads))
+                    }
+                    time => time,
+                };
+                scope.spawn(move || knowledge.tally(budget, &mut Random::from_seed(seed)))
+            }).collect();
+
+            handles.into_iter().map(|handle| handle.join().expect("sampler thread panicked")).collect()
+        });
+
+        let mut total = vec![0; knowledge.cells()];
+        for tally in tallies {
+            for (sum, count) in total.iter_mut().zip(tally) {
+                *sum += count;
+            }
+        }
+        total
+    }
+}
+
+impl Shooter for MonteCarloShooter {
+    fn choose_shot(&mut self, checked: &CheckField) -> Shot {
+        self.analyse(checked).0
+    }
+
+    /// The hottest cells of one heat map. The shots of a volley all land
+    /// before anything is learnt from them, so there's nothing better to
+    /// go on.
+    fn choose_volley(&mut self, checked: &CheckField, shots: usize) -> Vec<Shot> {
+        if shots == 1 {
+            return vec![self.choose_shot(checked)];
+        }
+
+        let knowledge = Knowledge::new(checked, &self.fleet);
+        let heat = self.sample_heat(&knowledge);
+        let candidates = knowledge.candidates();
+        let near_hits = knowledge.next_to_hits(&candidates);
+
+        // Without any heat, the cells next to wounded ships go first. Equal
+        // cells come in a random order.
+        let mut ranked = Vec::with_capacity(candidates.len());
+        for index in candidates {
+            ranked.push((self.score(&heat, index), near_hits.contains(&index), self.random.next_u64(), index));
+        }
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then(b.1.cmp(&a.1)).then(a.2.cmp(&b.2)));
+        let mut volley: Vec<usize> = ranked.into_iter().map(|(.., index)| index).take(shots).collect();
+
+        // Only when the candidates run out is a cell that can't hold a ship
+        // worth a shot.
+        let mut rest: Vec<usize> = (0..knowledge.cells())
+            .filter(|&index| {
+                let (x, y) = knowledge.cell(index);
+                matches!(checked.at(x, y), CheckedCell::Unchecked | CheckedCell::KnownEmpty) && !volley.contains(&index)
+            })
+            .collect();
+        while volley.len() < shots && !rest.is_empty() {
+            volley.push(rest.swap_remove(self.random.below(rest.len())));
+        }
+
+        volley.into_iter()
+            .map(|index| {
+                let (x, y) = knowledge.cell(index);
+                Shot { x, y }
+            })
+            .collect()
+    }
+
+    /// Wounded ships are finished off with plain shots. Otherwise a bomb
+    /// goes where it's expected to hit the most, a torpedo along the row
+    /// most likely to hold a ship, and sonar where the outcome is least
+    /// certain, in that order of preference, as long as each is worth the
+    /// turn.
+    fn choose_special(&mut self, checked: &CheckField, arsenal: Arsenal) -> Option<Special> {
+        let knowledge = Knowledge::new(checked, &self.fleet);
+        if !knowledge.hits.is_empty() || arsenal.is_empty() {
+            return None;
+        }
+
+        // Every accepted sample adds all the remaining ship cells to the
+        // heat, so this turns it into the chance of a ship on every cell.
+        let heat = self.sample_heat(&knowledge);
+        let total: u32 = heat.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let remaining: usize = knowledge.ships_left.iter().map(|shape| shape.size()).sum();
+        let chance = |x: usize, y: usize| -> f64 {
+            let index = knowledge.index(x, y);
+            if knowledge.blocked.get(index) { 0.0 } else { heat[index] as f64 * remaining as f64 / total as f64 }
+        };
+        let (width, height) = (knowledge.width, knowledge.height);
+        let centres = || (0..height).flat_map(move |y| (0..width).map(move |x| Shot { x, y }));
+        let best = |score: &dyn Fn(Shot) -> f64| -> Option<(f64, Shot)> {
+            centres().map(|centre| (score(centre), centre)).max_by(|a, b| a.0.total_cmp(&b.0))
+        };
+
+        if arsenal.count(Weapon::Bomb) > 0 {
+            let expected_hits = |centre: Shot| weapons::area(centre, width, height).map(|(x, y)| chance(x, y)).sum();
+            if let Some((hits, centre)) = best(&expected_hits) {
+                if hits >= BOMB_THRESHOLD {
+                    return Some(Special::Bomb(centre));
+                }
+            }
+        }
+
+        if arsenal.count(Weapon::Torpedo) > 0 {
+            // It runs past misses and into the first ship, so it hits
+            // unless every cell it crosses is open water. Land and the
+            // wrecks stop it as well.
+            let mut runs = Vec::new();
+            for row in 0..height {
+                for heading in [Heading::East, Heading::West] {
+                    let open_water: f64 = weapons::torpedo_path(row, heading, width)
+                        .take_while(|&(x, y)| !matches!(checked.at(x, y), CheckedCell::Hit | CheckedCell::Kill | CheckedCell::Island))
+                        .map(|(x, y)| 1.0 - chance(x, y))
+                        .product();
+                    runs.push((1.0 - open_water, row, heading));
+                }
+            }
+            let best_run = runs.into_iter().max_by(|a, b| a.0.total_cmp(&b.0));
+            if let Some((hit, row, heading)) = best_run {
+                if hit >= TORPEDO_THRESHOLD {
+                    return Some(Special::Torpedo { row, heading });
+                }
+            }
+        }
+
+        if arsenal.count(Weapon::Sonar) > 0 {
+            // Pinging the same place twice tells nothing new.
+            let pinged = |centre: Shot| {
+                checked.sonar_readings().iter().any(|reading| reading.centre.x == centre.x && reading.centre.y == centre.y)
+            };
+            let uncertainty = |centre: Shot| {
+                if pinged(centre) {
+                    return 0.0;
+                }
+                weapons::area(centre, width, height).map(|(x, y)| chance(x, y) * (1.0 - chance(x, y))).sum()
+            };
+            if let Some((uncertain, centre)) = best(&uncertainty) {
+                if uncertain >= SONAR_THRESHOLD {
+                    return Some(Special::Sonar(centre));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Everything the shooter has learnt from its `CheckField`.
+struct Knowledge {
+    width: usize,
+    height: usize,
+    touching: TouchingPolicy,
+    /// Cells that can't have an intact ship on them.
+    blocked: BitSet,
+    hits: BitSet,
+    ships_left: Vec<Shape>,
+    /// The cells every sonar ping covered and how many of them hold an
+    /// intact or wounded ship.
+    sonar: Vec<(Vec<usize>, usize)>,
+}
+
+impl Knowledge {
+    fn new(checked: &CheckField, fleet: &[Shape]) -> Knowledge {
+        let (width, height) = (checked.width(), checked.height());
+        let mut blocked = BitSet::new(width * height);
+        let mut hits = BitSet::new(width * height);
+        let mut ships_left = fleet.to_vec();
+
+        for y in 0..height {
+            for x in 0..width {
+                // The field already knows which cells the touching rules
+                // leave empty, so a kill only blocks its own cells here.
+                match checked.at(x, y) {
+                    CheckedCell::Unchecked => {}
+                    CheckedCell::Hit => hits.insert(y * width + x),
+                    CheckedCell::Miss | CheckedCell::Kill | CheckedCell::KnownEmpty
+                    | CheckedCell::Island | CheckedCell::Mine => blocked.insert(y * width + x),
+                }
+            }
+        }
+
+        for ship in checked.sunk_ships() {
+            if let Some(position) = ships_left.iter().position(|left| *left == ship.shape) {
+                ships_left.swap_remove(position);
+            }
+        }
+        ships_left.sort_by_key(|shape| std::cmp::Reverse(shape.size()));
+
+        // Sunk ships aren't sampled, so what they add to a reading is
+        // taken off it.
+        let sonar = checked.sonar_readings().iter()
+            .map(|reading| {
+                let area: Vec<(usize, usize)> = weapons::area(reading.centre, width, height).collect();
+                let sunk = area.iter().filter(|&&(x, y)| checked.at(x, y) == CheckedCell::Kill).count();
+                let cells = area.into_iter().map(|(x, y)| y * width + x).collect();
+                (cells, reading.ship_cells.saturating_sub(sunk))
+            })
+            .collect();
+
+        Knowledge { width, height, touching: checked.touching(), blocked, hits, ships_left, sonar }
+    }
+
+    fn cells(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// The index of `(x, y)`, row by row.
+    fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    /// The `(x, y)` of the cell at `index`.
+    fn cell(&self, index: usize) -> (usize, usize) {
+        (index % self.width, index / self.width)
+    }
+
+    /// Cells worth shooting at.
+    fn candidates(&self) -> Vec<usize> {
+        (0..self.cells())
+            .filter(|&index| !self.blocked.get(index) && !self.hits.get(index))
+            .collect()
+    }
+
+    fn next_to_hits(&self, candidates: &[usize]) -> Vec<usize> {
+        candidates.iter().copied().filter(|&index| {
+            let (x, y) = self.cell(index);
+            neighbours(x, y, self.width, self.height).any(|(nx, ny)| (nx == x) != (ny == y) && self.hits.get(self.index(nx, ny)))
+        }).collect()
+    }
+
+    fn tally(&self, budget: Budget, random: &mut Random) -> Vec<u32> {
+        let mut tally = vec![0; self.cells()];
+        let mut attempts = 0usize;
+        let mut accepted = 0usize;
+
+        let deadline = match budget {
+            Budget::Time(duration) => Some(Instant::now() + duration),
+            Budget::Samples(_) => None,
+        };
+
+        loop {
+            match budget {
+                Budget::Samples(count) => {
+                    // Give up eventually if the field is (nearly) inconsistent.
+                    if accepted >= count || attempts >= count.saturating_mul(50).max(1000) {
+                        break;
+                    }
+                }
+                Budget::Time(_) => {
+                    if attempts.is_multiple_of(32) && deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        break;
+                    }
+                }
+            }
+
+            attempts += 1;
+            if self.sample(random, &mut tally) {
+                accepted += 1;
+            }
+        }
+
+        tally
+    }
+
+    /// Tries to generate one fleet layout that agrees with the knowledge
+    /// and adds its unknown ship cells to `tally`.
+    fn sample(&self, random: &mut Random, tally: &mut [u32]) -> bool {
+        let mut field = BitPlayerField::with_touching(self.width, self.height, self.touching);
+        let mut left = self.ships_left.clone();
+        let mut placed = Vec::with_capacity(left.len());
+
+        // Every hit has to be covered by some ship, so place those first.
+        while let Some(hit) = self.hits.iter().find(|&index| {
+            let (x, y) = self.cell(index);
+            field.at(x, y) != FieldCell::Ship
+        }) {
+            if left.is_empty() {
+                return false;
+            }
+
+            let ship = (0..ATTEMPTS_PER_SHIP).find_map(|_| {
+                let which = random.below(left.len());
+                let ship = self.ship_through(hit, left[which], random)?;
+                self.fits(&field, &ship).then_some((which, ship))
+            });
+
+            match ship {
+                Some((which, ship)) => {
+                    left.swap_remove(which);
+                    field.place_ship(&ship);
+                    placed.push(ship);
+                }
+                None => return false,
+            }
+        }
+
+        left.sort_by_key(|shape| std::cmp::Reverse(shape.size()));
+        for shape in left {
+            let orientations = shape.orientations();
+            let ship = (0..ATTEMPTS_PER_SHIP).find_map(|_| {
+                let (rotation, mirrored) = orientations[random.below(orientations.len())];
+                let ship = Ship {
+                    shape,
+                    x: random.below(self.width),
+                    y: random.below(self.height),
+                    rotation,
+                    mirrored,
+                };
+                self.fits(&field, &ship).then_some(ship)
+            });
+
+            match ship {
+                Some(ship) => {
+                    field.place_ship(&ship);
+                    placed.push(ship);
+                }
+                None => return false,
+            }
+        }
+
+        // A ship that is hit everywhere would have been reported as killed.
+        if placed.iter().any(|ship| ship.cells().all(|(x, y)| self.hits.get(self.index(x, y)))) {
+            return false;
+        }
+        // And the sonar must find the ships it found in the real game.
+        for (area, ship_cells) in &self.sonar {
+            let found = area.iter().filter(|&&index| {
+                let (x, y) = self.cell(index);
+                field.at(x, y) == FieldCell::Ship
+            }).count();
+            if found != *ship_cells {
+                return false;
+            }
+        }
+
+        for ship in &placed {
+            for (x, y) in ship.cells() {
+                let index = self.index(x, y);
+                if !self.hits.get(index) {
+                    tally[index] += 1;
+                }
+            }
+        }
+        true
+    }
+
+    /// A random ship of `shape` that goes through the cell `index`.
+    fn ship_through(&self, index: usize, shape: Shape, random: &mut Random) -> Option<Ship> {
+        let (x, y) = self.cell(index);
+        let orientations = shape.orientations();
+        let (rotation, mirrored) = orientations[random.below(orientations.len())];
+        let (offset_x, offset_y) = shape.cells(rotation, mirrored).nth(random.below(shape.size()))?;
+
+        Some(Ship { shape, x: x.checked_sub(offset_x)?, y: y.checked_sub(offset_y)?, rotation, mirrored })
+    }
+
+    fn fits(&self, field: &BitPlayerField, ship: &Ship) -> bool {
+        field.can_place(ship).is_ok() && ship.cells().all(|(x, y)| !self.blocked.get(self.index(x, y)))
+    }
+}
+
+/// The cell itself and all eight cells around it that are on the field.
+fn neighbours(x: usize, y: usize, width: usize, height: usize) -> impl Iterator<Item = (usize, usize)> {
+    let xs = x.saturating_sub(1)..=(x + 1).min(width - 1);
+    xs.flat_map(move |nx| (y.saturating_sub(1)..=(y + 1).min(height - 1)).map(move |ny| (nx, ny)))
+}
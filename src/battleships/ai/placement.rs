@@ -0,0 +1,282 @@
+use crate::battleships::{
+    bitboard::BitPlayerField,
+    feasibility::{self, FeasibilityError},
+    field::FieldCell,
+    rules::{Rules, FieldSize},
+    shape::Shape,
+    ship::{Ship, Rotation},
+};
+use crate::utilities::random::Random;
+
+use super::profile::Profile;
+
+/// How many placement attempts a single ship gets before the layout is
+/// started over.
+const ATTEMPTS_PER_SHIP: usize = 1000;
+/// How many times a layout is started over before settling for whatever
+/// the feasibility solver comes up with. Only tight fleets get there.
+const RETRIES: usize = 100;
+
+/// Something that decides where the computer hides its fleet.
+pub trait Placer {
+    /// Fails only for fleets that `random_layout` can't place.
+    fn place_fleet(&mut self, rules: &Rules) -> Result<Vec<Ship>, FeasibilityError>;
+}
+
+/// Puts every ship in a uniformly random legal spot.
+pub struct RandomPlacer {
+    random: Random,
+}
+
+impl RandomPlacer {
+    pub fn new() -> RandomPlacer {
+        RandomPlacer { random: Random::new() }
+    }
+}
+
+impl Default for RandomPlacer {
+    fn default() -> Self {
+        RandomPlacer::new()
+    }
+}
+
+impl Placer for RandomPlacer {
+    fn place_fleet(&mut self, rules: &Rules) -> Result<Vec<Ship>, FeasibilityError> {
+        random_layout(rules, &rules.fleet, &mut self.random)
+    }
+}
+
+/// Generates a bunch of random layouts and keeps the one that a given
+/// human is least likely to shoot at early on.
+pub struct AdaptivePlacer {
+    shot_prior: Vec<f64>,
+    candidates: usize,
+    random: Random,
+}
+
+impl AdaptivePlacer {
+    pub fn new(profile: &Profile) -> AdaptivePlacer {
+        AdaptivePlacer {
+            shot_prior: profile.shot_prior(),
+            candidates: 200,
+            random: Random::new(),
+        }
+    }
+}
+
+impl Placer for AdaptivePlacer {
+    fn place_fleet(&mut self, rules: &Rules) -> Result<Vec<Ship>, FeasibilityError> {
+        let Rules { size, fleet, .. } = rules;
+        let danger = |ships: &Vec<Ship>| -> f64 {
+            ships.iter()
+                .flat_map(|ship| ship.cells())
+                .map(|(x, y)| self.shot_prior[y * size.width + x])
+                .sum()
+        };
+
+        let mut best = random_layout(rules, fleet, &mut self.random)?;
+        let mut best_danger = danger(&best);
+
+        for _ in 1..self.candidates {
+            let layout = random_layout(rules, fleet, &mut self.random)?;
+            let layout_danger = danger(&layout);
+            if layout_danger < best_danger {
+                best = layout;
+                best_danger = layout_danger;
+            }
+        }
+
+        Ok(best)
+    }
+}
+
+/// Hides the fleet from good shooters. Density based shooters love the
+/// middle of the field and parity hunters sweep one checkerboard colour
+/// first, so the long ships go where the density is low and the one-cell
+/// ships are spread into empty regions, off the colour swept first.
+pub struct SmartPlacer {
+    avoid_centre: bool,
+    spread_singles: bool,
+    anti_parity: bool,
+    candidates: usize,
+    random: Random,
+}
+
+impl SmartPlacer {
+    pub fn new(avoid_centre: bool, spread_singles: bool, anti_parity: bool) -> SmartPlacer {
+        SmartPlacer {
+            avoid_centre,
+            spread_singles,
+            anti_parity,
+            candidates: 300,
+            random: Random::new(),
+        }
+    }
+
+    fn place_singles(&mut self, rules: &Rules, long_ships: &[Ship], singles: usize, heat: &[f64]) -> Option<Vec<Ship>> {
+        let FieldSize { width, height } = rules.size;
+        let mut field = island_field(rules);
+        for ship in long_ships {
+            field.place_ship(ship);
+        }
+
+        // Which colour gets swept first is anyone's guess, so pick one.
+        let swept_colour = self.random.below(2);
+        let mut ships = Vec::with_capacity(singles);
+
+        for _ in 0..singles {
+            let taken: Vec<(usize, usize)> = long_ships.iter().chain(&ships).flat_map(|ship| ship.cells()).collect();
+            let mut best: Option<(f64, Ship)> = None;
+
+            for y in 0..height {
+                for x in 0..width {
+                    let ship = Ship::line(1, x, y, Rotation::Horizontal);
+                    if field.can_place(&ship).is_err() {
+                        continue;
+                    }
+
+                    let mut score = self.random.below(1000) as f64 / 10_000.0;
+                    if self.spread_singles {
+                        let distance = taken.iter()
+                            .map(|&(other_x, other_y)| x.abs_diff(other_x).max(y.abs_diff(other_y)))
+                            .min()
+                            .unwrap_or(width.max(height));
+                        score += distance.min(5) as f64;
+                    }
+                    if self.anti_parity && (x + y) % 2 == swept_colour {
+                        score -= 1.5;
+                    }
+                    if self.avoid_centre {
+                        score -= heat[y * width + x];
+                    }
+
+                    if best.as_ref().is_none_or(|(best_score, _)| score > *best_score) {
+                        best = Some((score, ship));
+                    }
+                }
+            }
+
+            let (_, ship) = best?;
+            field.place_ship(&ship);
+            ships.push(ship);
+        }
+
+        Some(ships)
+    }
+}
+
+impl Placer for SmartPlacer {
+    fn place_fleet(&mut self, rules: &Rules) -> Result<Vec<Ship>, FeasibilityError> {
+        let Rules { size, fleet, .. } = rules;
+        let size = *size;
+        let long: Vec<Shape> = fleet.iter().copied().filter(|shape| shape.size() > 1).collect();
+        let singles = fleet.len() - long.len();
+        let heat = density(size, fleet);
+
+        let centre_penalty = |ships: &Vec<Ship>| -> f64 {
+            ships.iter().flat_map(|ship| ship.cells()).map(|(x, y)| heat[y * size.width + x]).sum()
+        };
+
+        for _ in 0..RETRIES {
+            let mut long_ships = random_layout(rules, &long, &mut self.random)?;
+            if self.avoid_centre {
+                let mut best_penalty = centre_penalty(&long_ships);
+                for _ in 1..self.candidates {
+                    let layout = random_layout(rules, &long, &mut self.random)?;
+                    let penalty = centre_penalty(&layout);
+                    if penalty < best_penalty {
+                        long_ships = layout;
+                        best_penalty = penalty;
+                    }
+                }
+            }
+
+            // The long ships may leave no room for the small ones, then
+            // it's best to just start over.
+            if let Some(single_ships) = self.place_singles(rules, &long_ships, singles, &heat) {
+                long_ships.extend(single_ships);
+                return Ok(long_ships);
+            }
+        }
+
+        random_layout(rules, fleet, &mut self.random)
+    }
+}
+
+/// How many ways the fleet's ships can cover every cell of an empty
+/// field, scaled so the hottest cell is `1.0`. This is what a density
+/// based shooter sees before its first shot.
+pub fn density(size: FieldSize, fleet: &[Shape]) -> Vec<f64> {
+    let FieldSize { width, height } = size;
+    let mut heat = vec![0; width * height];
+
+    for &shape in fleet {
+        for (rotation, mirrored) in shape.orientations() {
+            for y in 0..height {
+                for x in 0..width {
+                    let ship = Ship { shape, x, y, rotation, mirrored };
+                    if ship.cells().all(|(x, y)| x < width && y < height) {
+                        for (x, y) in ship.cells() {
+                            heat[y * width + x] += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let max = heat.iter().copied().max().unwrap_or(0).max(1) as f64;
+    heat.into_iter().map(|count| count as f64 / max).collect()
+}
+
+/// An empty field of `rules`, with only the islands on it.
+fn island_field(rules: &Rules) -> BitPlayerField {
+    let FieldSize { width, height } = rules.size;
+    let mut field = BitPlayerField::with_touching(width, height, rules.touching);
+    for &(x, y) in &rules.islands {
+        field.set(x, y, FieldCell::Island);
+    }
+    field
+}
+
+/// A random legal layout of `fleet` on the field of `rules`, around its
+/// islands. Larger ships are placed first since they are the hardest to
+/// fit. Fleets that barely fit get the solver's layout if random ones
+/// keep failing, and fail with it if there is none.
+pub fn random_layout(rules: &Rules, fleet: &[Shape], random: &mut Random) -> Result<Vec<Ship>, FeasibilityError> {
+    let size = rules.size;
+    let mut shapes = fleet.to_vec();
+    shapes.sort_by_key(|shape| std::cmp::Reverse(shape.size()));
+
+    'retry: for _ in 0..RETRIES {
+        let mut field = island_field(rules);
+        let mut ships = Vec::with_capacity(shapes.len());
+
+        for &shape in &shapes {
+            let orientations = shape.orientations();
+            let ship = (0..ATTEMPTS_PER_SHIP).find_map(|_| {
+                let (rotation, mirrored) = orientations[random.below(orientations.len())];
+                let ship = Ship {
+                    shape,
+                    x: random.below(size.width),
+                    y: random.below(size.height),
+                    rotation,
+                    mirrored,
+                };
+                field.can_place(&ship).is_ok().then_some(ship)
+            });
+
+            match ship {
+                Some(ship) => {
+                    field.place_ship(&ship);
+                    ships.push(ship);
+                }
+                None => continue 'retry,
+            }
+        }
+
+        return Ok(ships);
+    }
+
+    feasibility::solve(&Rules { fleet: fleet.to_vec(), mines: 0, ..rules.clone() })
+}
@@ -0,0 +1,192 @@
+use std::{fs, io, path::PathBuf, fmt::Display};
+
+use crate::battleships::{
+    field::{PlayerField, FieldCell},
+    rules::FieldSize,
+    shot::Shot,
+};
+
+const PROFILE_DIRECTORY: &str = "profiles";
+const PROFILE_VERSION: u32 = 1;
+
+/// How many of the opponent's opening shots are remembered every game.
+pub const REMEMBERED_SHOTS: usize = 20;
+
+/// What we know about one human opponent from earlier games: where they
+/// hid their ships and where they like to shoot first.
+pub struct Profile {
+    name: String,
+    size: FieldSize,
+    games: u32,
+    ship_heat: Vec<u32>,
+    shot_heat: Vec<u32>,
+}
+
+impl Profile {
+    pub fn new(name: &str, size: FieldSize) -> Profile {
+        Profile {
+            name: name.to_string(),
+            size,
+            games: 0,
+            ship_heat: vec![0; size.cells()],
+            shot_heat: vec![0; size.cells()],
+        }
+    }
+
+    /// Loads the profile of `name`, or starts a new one if this is the
+    /// first game against them.
+    pub fn load(name: &str, size: FieldSize) -> Result<Profile, ProfileError> {
+        let contents = match fs::read_to_string(profile_path(name)) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Profile::new(name, size)),
+            Err(error) => return Err(ProfileError::IOError(error)),
+        };
+
+        let profile = Profile::parse(name, &contents)?;
+        if profile.size != size {
+            return Err(ProfileError::WrongSize(profile.size));
+        }
+        Ok(profile)
+    }
+
+    pub fn save(&self) -> Result<(), ProfileError> {
+        fs::create_dir_all(PROFILE_DIRECTORY).map_err(ProfileError::IOError)?;
+        fs::write(profile_path(&self.name), self.to_string()).map_err(ProfileError::IOError)
+    }
+
+    pub fn games(&self) -> u32 {
+        self.games
+    }
+
+    /// How much to trust the heat maps, from `0.0` for a new opponent up
+    /// to `1.0` after ten games.
+    pub fn confidence(&self) -> f64 {
+        self.games.min(10) as f64 / 10.0
+    }
+
+    /// Remembers the opponent's revealed fleet and their opening shots.
+    pub fn record_game(&mut self, field: &PlayerField, shots: &[Shot]) {
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                if matches!(field.at(x, y), FieldCell::Ship | FieldCell::Hit | FieldCell::Dead) {
+                    self.ship_heat[y * self.size.width + x] += 1;
+                }
+            }
+        }
+
+        for shot in shots.iter().take(REMEMBERED_SHOTS) {
+            self.shot_heat[shot.y * self.size.width + shot.x] += 1;
+        }
+
+        self.games += 1;
+    }
+
+    /// How likely every cell is to hold one of their ships, scaled so the
+    /// most popular cell is `1.0`.
+    pub fn ship_prior(&self) -> Vec<f64> {
+        normalized(&self.ship_heat)
+    }
+
+    /// How likely they are to shoot every cell early, scaled so the most
+    /// popular cell is `1.0`.
+    pub fn shot_prior(&self) -> Vec<f64> {
+        normalized(&self.shot_heat)
+    }
+
+    fn parse(name: &str, contents: &str) -> Result<Profile, ProfileError> {
+        let mut lines = contents.lines().filter(|line| !line.starts_with('#') && !line.trim().is_empty());
+
+        let version: u32 = read_field(&mut lines, "version")?;
+        if version != PROFILE_VERSION {
+            return Err(ProfileError::UnknownVersion(version));
+        }
+        let size: FieldSize = read_field(&mut lines, "size")?;
+        let games: u32 = read_field(&mut lines, "games")?;
+
+        expect_line(&mut lines, "ships")?;
+        let ship_heat = read_grid(&mut lines, size)?;
+        expect_line(&mut lines, "shots")?;
+        let shot_heat = read_grid(&mut lines, size)?;
+
+        Ok(Profile { name: name.to_string(), size, games, ship_heat, shot_heat })
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "# battleships profile of {}", self.name)?;
+        writeln!(f, "version {PROFILE_VERSION}")?;
+        writeln!(f, "size {}", self.size)?;
+        writeln!(f, "games {}", self.games)?;
+
+        for (title, grid) in [("ships", &self.ship_heat), ("shots", &self.shot_heat)] {
+            writeln!(f, "{title}")?;
+            for row in grid.chunks(self.size.width) {
+                let row: Vec<String> = row.iter().map(|count| count.to_string()).collect();
+                writeln!(f, "{}", row.join(" "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn profile_path(name: &str) -> PathBuf {
+    let file_name: String = name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    PathBuf::from(PROFILE_DIRECTORY).join(format!("{file_name}.txt"))
+}
+
+fn normalized(heat: &[u32]) -> Vec<f64> {
+    let max = heat.iter().copied().max().unwrap_or(0).max(1) as f64;
+    heat.iter().map(|count| *count as f64 / max).collect()
+}
+
+fn read_field<'a, T: std::str::FromStr>(lines: &mut impl Iterator<Item = &'a str>, name: &str) -> Result<T, ProfileError> {
+    let line = lines.next().ok_or(ProfileError::Corrupt(format!("missing '{name}'")))?;
+
+    line.strip_prefix(name)
+        .and_then(|value| value.trim().parse().ok())
+        .ok_or(ProfileError::Corrupt(format!("expected '{name} <value>', got '{line}'")))
+}
+
+fn expect_line<'a>(lines: &mut impl Iterator<Item = &'a str>, expected: &str) -> Result<(), ProfileError> {
+    match lines.next() {
+        Some(line) if line.trim() == expected => Ok(()),
+        _ => Err(ProfileError::Corrupt(format!("missing '{expected}' section"))),
+    }
+}
+
+fn read_grid<'a>(lines: &mut impl Iterator<Item = &'a str>, size: FieldSize) -> Result<Vec<u32>, ProfileError> {
+    let mut grid = Vec::with_capacity(size.cells());
+
+    for _ in 0..size.height {
+        let line = lines.next().ok_or(ProfileError::Corrupt("grid is too short".to_string()))?;
+        let row: Result<Vec<u32>, _> = line.split_whitespace().map(str::parse).collect();
+
+        match row {
+            Ok(row) if row.len() == size.width => grid.extend(row),
+            _ => return Err(ProfileError::Corrupt(format!("bad grid row '{line}'"))),
+        }
+    }
+
+    Ok(grid)
+}
+
+pub enum ProfileError {
+    IOError(io::Error),
+    UnknownVersion(u32),
+    WrongSize(FieldSize),
+    Corrupt(String),
+}
+
+impl Display for ProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileError::IOError(inner) => write!(f, "can't access the profile: {inner}"),
+            ProfileError::UnknownVersion(version) => write!(f, "unknown profile version {version}"),
+            ProfileError::WrongSize(size) => write!(f, "the profile was recorded on a {}x{} field", size.width, size.height),
+            ProfileError::Corrupt(reason) => write!(f, "the profile is corrupt: {reason}"),
+        }
+    }
+}
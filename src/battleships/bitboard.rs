@@ -0,0 +1,504 @@
+use super::field::{FieldCell, CheckedCell, ShipPlacementError};
+use super::shape::Shape;
+use super::ship::Ship;
+use super::rules::TouchingPolicy;
+
+const WORD_BITS: usize = 64;
+
+/// A fixed size set of cell indices packed into `u64` words.
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    pub fn new(bits: usize) -> BitSet {
+        BitSet {
+            words: vec![0; bits.div_ceil(WORD_BITS)],
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> bool {
+        self.words[index / WORD_BITS] & (1 << (index % WORD_BITS)) != 0
+    }
+
+    #[inline]
+    pub fn insert(&mut self, index: usize) {
+        self.words[index / WORD_BITS] |= 1 << (index % WORD_BITS);
+    }
+
+    #[inline]
+    pub fn remove(&mut self, index: usize) {
+        self.words[index / WORD_BITS] &= !(1 << (index % WORD_BITS));
+    }
+
+    /// Inserts every index in `start..end`.
+    pub fn insert_range(&mut self, start: usize, end: usize) {
+        let mut index = start;
+        while index < end {
+            let word = index / WORD_BITS;
+            let offset = index % WORD_BITS;
+            let span = (WORD_BITS - offset).min(end - index);
+            let mask = if span == WORD_BITS { u64::MAX } else { (1 << span) - 1 };
+
+            self.words[word] |= mask << offset;
+            index += span;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.words.fill(0);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn union_with(&mut self, other: &BitSet) {
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word |= other;
+        }
+    }
+
+    pub fn intersects(&self, other: &BitSet) -> bool {
+        self.words.iter().zip(&other.words).any(|(word, other)| word & other != 0)
+    }
+
+    /// The first index in `start..end` that is in the set. Works a whole
+    /// word at a time, so a ship-sized range is usually one or two `and`s.
+    pub fn first_in_range(&self, start: usize, end: usize) -> Option<usize> {
+        let mut index = start;
+        while index < end {
+            let word = index / WORD_BITS;
+            let offset = index % WORD_BITS;
+            let span = (WORD_BITS - offset).min(end - index);
+            let mask = if span == WORD_BITS { u64::MAX } else { (1 << span) - 1 };
+
+            let bits = self.words[word] & (mask << offset);
+            if bits != 0 {
+                return Some(word * WORD_BITS + bits.trailing_zeros() as usize);
+            }
+            index += span;
+        }
+        None
+    }
+
+    /// The bits in `start..end`, shifted down so that `start` is bit 0.
+    pub fn slice(&self, start: usize, end: usize) -> Vec<u64> {
+        let mut slice = vec![0; (end - start).div_ceil(WORD_BITS)];
+        let shift = start % WORD_BITS;
+
+        for (i, word) in slice.iter_mut().enumerate() {
+            let first = start / WORD_BITS + i;
+            let low = self.words.get(first).copied().unwrap_or(0) >> shift;
+            let high = match shift {
+                0 => 0,
+                _ => self.words.get(first + 1).copied().unwrap_or(0) << (WORD_BITS - shift),
+            };
+            *word = low | high;
+        }
+
+        let tail = (end - start) % WORD_BITS;
+        if let (Some(last), true) = (slice.last_mut(), tail != 0) {
+            *last &= (1 << tail) - 1;
+        }
+        slice
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, word)| {
+            let mut word = *word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(i * WORD_BITS + bit)
+            })
+        })
+    }
+}
+
+/// Same as `PlayerField`, but every cell state is a bit mask. Ships keep
+/// the cells the touching policy closes to other ships in two masks, one
+/// row-major and one column-major, so both horizontal and vertical
+/// placement checks are a single range test.
+#[derive(Clone)]
+pub struct BitPlayerField {
+    ships: BitSet,
+    hits: BitSet,
+    dead: BitSet,
+    misses: BitSet,
+    islands: BitSet,
+    /// Mines, whether they went off (then they are in `misses` too) or not.
+    mines: BitSet,
+    halo: BitSet,
+    halo_transposed: BitSet,
+    width: usize,
+    height: usize,
+    touching: TouchingPolicy,
+}
+
+impl BitPlayerField {
+    pub fn new(width: usize, height: usize) -> BitPlayerField {
+        BitPlayerField::with_touching(width, height, TouchingPolicy::NoTouch)
+    }
+
+    pub fn with_touching(width: usize, height: usize, touching: TouchingPolicy) -> BitPlayerField {
+        let cells = width * height;
+
+        BitPlayerField {
+            ships: BitSet::new(cells),
+            hits: BitSet::new(cells),
+            dead: BitSet::new(cells),
+            misses: BitSet::new(cells),
+            islands: BitSet::new(cells),
+            mines: BitSet::new(cells),
+            halo: BitSet::new(cells),
+            halo_transposed: BitSet::new(cells),
+            width,
+            height,
+            touching,
+        }
+    }
+
+    #[inline]
+    fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    /// The index of `(x, y)` in the column-major masks.
+    #[inline]
+    fn transposed_index(&self, x: usize, y: usize) -> usize {
+        x * self.height + y
+    }
+
+    #[inline]
+    pub fn at(&self, x: usize, y: usize) -> FieldCell {
+        assert!(x < self.width && y < self.height);
+        let index = self.index(x, y);
+        let word = index / WORD_BITS;
+        let bit = 1 << (index % WORD_BITS);
+
+        if self.ships.words[word] & bit != 0 {
+            if self.dead.words[word] & bit != 0 {
+                FieldCell::Dead
+            } else if self.hits.words[word] & bit != 0 {
+                FieldCell::Hit
+            } else {
+                FieldCell::Ship
+            }
+        } else if self.islands.words[word] & bit != 0 {
+            FieldCell::Island
+        } else if self.mines.words[word] & bit != 0 {
+            if self.misses.words[word] & bit != 0 { FieldCell::Crater } else { FieldCell::Mine }
+        } else if self.misses.words[word] & bit != 0 {
+            FieldCell::EnemyMiss
+        } else {
+            FieldCell::Empty
+        }
+    }
+
+    /// Overwrites a single cell. Note that the halo only ever grows:
+    /// removing a ship cell this way does not free its neighbours.
+    #[inline]
+    pub fn set(&mut self, x: usize, y: usize, value: FieldCell) {
+        let index = self.index(x, y);
+        self.ships.remove(index);
+        self.hits.remove(index);
+        self.dead.remove(index);
+        self.misses.remove(index);
+        self.islands.remove(index);
+        self.mines.remove(index);
+
+        match value {
+            FieldCell::Empty => {}
+            FieldCell::Island => self.islands.insert(index),
+            FieldCell::Mine => self.mines.insert(index),
+            FieldCell::Crater => {
+                self.mines.insert(index);
+                self.misses.insert(index);
+            }
+            FieldCell::Ship => {
+                self.ships.insert(index);
+                self.add_halo(x, y);
+            }
+            FieldCell::EnemyMiss => self.misses.insert(index),
+            FieldCell::Hit => {
+                self.ships.insert(index);
+                self.hits.insert(index);
+            }
+            FieldCell::Dead => {
+                self.ships.insert(index);
+                self.dead.insert(index);
+            }
+        }
+    }
+
+    /// Whether `ship` still has cells that weren't hit, once `(x, y)` is.
+    pub fn will_ship_survive_after_shot(&self, ship: &Ship, x: usize, y: usize) -> bool {
+        ship.cells().any(|cell| cell != (x, y) && self.at(cell.0, cell.1) == FieldCell::Ship)
+    }
+
+    pub fn mark_dead(&mut self, ship: &Ship) {
+        for (x, y) in ship.cells() {
+            let index = self.index(x, y);
+            self.hits.remove(index);
+            self.dead.insert(index);
+        }
+    }
+
+    pub fn mark_hit(&mut self, x: usize, y: usize) {
+        let index = self.index(x, y);
+        self.ships.insert(index);
+        self.hits.insert(index);
+    }
+
+    pub fn mark_enemy_miss(&mut self, x: usize, y: usize) {
+        let index = self.index(x, y);
+        self.misses.insert(index);
+    }
+
+    pub fn is_ship_on(&self, x: usize, y: usize) -> bool {
+        self.at(x, y) == FieldCell::Ship
+    }
+
+    pub fn has_ships_left(&self) -> bool {
+        self.ships.words.iter()
+            .zip(&self.hits.words)
+            .zip(&self.dead.words)
+            .any(|((ships, hits), dead)| ships & !hits & !dead != 0)
+    }
+
+    pub fn place_ship(&mut self, ship: &Ship) {
+        debug_assert!(self.can_place(ship).is_ok());
+
+        let (width, height) = match (ship.shape, ship.rotation.is_horizontal()) {
+            (Shape::Line(length), true) => (length, 1),
+            (Shape::Line(length), false) => (1, length),
+            // Shaped ships don't fill their box, so they go cell by cell.
+            _ => {
+                for (x, y) in ship.cells() {
+                    let index = self.index(x, y);
+                    self.ships.insert(index);
+                    self.add_halo(x, y);
+                }
+                return;
+            }
+        };
+
+        for y in ship.y..ship.y + height {
+            let start = self.index(ship.x, y);
+            self.ships.insert_range(start, start + width);
+        }
+
+        // Without the no-touch rule the halo isn't a rectangle.
+        if self.touching != TouchingPolicy::NoTouch {
+            for (x, y) in ship.cells() {
+                self.add_halo(x, y);
+            }
+            return;
+        }
+
+        let left = ship.x.saturating_sub(1);
+        let right = (ship.x + width + 1).min(self.width);
+        let top = ship.y.saturating_sub(1);
+        let bottom = (ship.y + height + 1).min(self.height);
+
+        for y in top..bottom {
+            self.halo.insert_range(self.index(left, y), self.index(right, y));
+        }
+        for x in left..right {
+            self.halo_transposed.insert_range(self.transposed_index(x, top), self.transposed_index(x, bottom));
+        }
+    }
+
+    pub fn can_place(&self, ship: &Ship) -> Result<(), ShipPlacementError> {
+        for (x, y) in ship.cells() {
+            if x >= self.width {
+                return Err(ShipPlacementError::OutOfBoundsX(self.width));
+            }
+            if y >= self.height {
+                return Err(ShipPlacementError::OutOfBoundsY(self.height));
+            }
+            if self.islands.get(self.index(x, y)) {
+                return Err(ShipPlacementError::OnIsland(x, y));
+            }
+            if self.mines.get(self.index(x, y)) {
+                return Err(ShipPlacementError::OnMine(x, y));
+            }
+        }
+
+        let blocked = match (ship.shape, ship.rotation.is_horizontal()) {
+            (Shape::Line(length), true) => {
+                let start = self.index(ship.x, ship.y);
+                self.halo.first_in_range(start, start + length)
+                    .map(|index| (index % self.width, index / self.width))
+            }
+            (Shape::Line(length), false) => {
+                let start = self.transposed_index(ship.x, ship.y);
+                self.halo_transposed.first_in_range(start, start + length)
+                    .map(|index| (index / self.height, index % self.height))
+            }
+            _ => ship.cells().find(|&(x, y)| self.halo.get(self.index(x, y))),
+        };
+
+        match blocked {
+            Some((x, y)) => {
+                let (other_x, other_y) = self.neighbouring_ship(x, y)
+                    .expect("every halo cell borders a ship");
+                Err(ShipPlacementError::TouchesWithShip(other_x, other_y))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn add_halo(&mut self, x: usize, y: usize) {
+        for (nx, ny) in self.neighbourhood(x, y) {
+            let index = self.index(nx, ny);
+            self.halo.insert(index);
+            self.halo_transposed.insert(self.transposed_index(nx, ny));
+        }
+    }
+
+    fn neighbouring_ship(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        self.neighbourhood(x, y)
+            .find(|(nx, ny)| self.ships.get(self.index(*nx, *ny)))
+    }
+
+    /// The cell itself and the cells around it that the touching policy
+    /// keeps free of other ships, as far as they are on the board.
+    fn neighbourhood(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> {
+        let (width, height) = (self.width as isize, self.height as isize);
+        std::iter::once((0, 0))
+            .chain(self.touching.empty_around_kill().iter().copied())
+            .map(move |(dx, dy)| (x as isize + dx, y as isize + dy))
+            .filter(move |&(nx, ny)| nx >= 0 && ny >= 0 && nx < width && ny < height)
+            .map(|(nx, ny)| (nx as usize, ny as usize))
+    }
+}
+
+/// Same as `CheckField`, stored as one mask per marker.
+#[derive(Clone)]
+pub struct BitCheckField {
+    misses: BitSet,
+    hits: BitSet,
+    kills: BitSet,
+    known_empty: BitSet,
+    islands: BitSet,
+    mines: BitSet,
+    width: usize,
+    height: usize,
+    touching: TouchingPolicy,
+    bent_ships: bool,
+}
+
+impl BitCheckField {
+    pub fn new(width: usize, height: usize) -> BitCheckField {
+        BitCheckField::with_touching(width, height, TouchingPolicy::NoTouch)
+    }
+
+    pub fn with_touching(width: usize, height: usize, touching: TouchingPolicy) -> BitCheckField {
+        let cells = width * height;
+
+        BitCheckField {
+            misses: BitSet::new(cells),
+            hits: BitSet::new(cells),
+            kills: BitSet::new(cells),
+            known_empty: BitSet::new(cells),
+            islands: BitSet::new(cells),
+            mines: BitSet::new(cells),
+            width,
+            height,
+            touching,
+            bent_ships: false,
+        }
+    }
+
+    /// Same as `CheckField::allow_bent_ships`.
+    pub fn allow_bent_ships(&mut self) {
+        self.bent_ships = true;
+    }
+
+    pub fn mark(&mut self, x: usize, y: usize, marker: CheckedCell) {
+        let index = y * self.width + x;
+        self.misses.remove(index);
+        self.hits.remove(index);
+        self.kills.remove(index);
+        self.known_empty.remove(index);
+        self.islands.remove(index);
+        self.mines.remove(index);
+
+        match marker {
+            CheckedCell::Unchecked => {}
+            CheckedCell::Miss => self.misses.insert(index),
+            CheckedCell::Hit => {
+                self.hits.insert(index);
+                if !self.bent_ships {
+                    self.mark_known_empty_around(x, y, self.touching.empty_around_hit());
+                }
+            }
+            CheckedCell::Kill => self.kills.insert(index),
+            CheckedCell::KnownEmpty => self.known_empty.insert(index),
+            CheckedCell::Island => self.islands.insert(index),
+            CheckedCell::Mine => self.mines.insert(index),
+        }
+    }
+
+    pub fn at(&self, x: usize, y: usize) -> CheckedCell {
+        assert!(x < self.width && y < self.height);
+        let index = y * self.width + x;
+
+        if self.kills.get(index) {
+            CheckedCell::Kill
+        } else if self.hits.get(index) {
+            CheckedCell::Hit
+        } else if self.misses.get(index) {
+            CheckedCell::Miss
+        } else if self.known_empty.get(index) {
+            CheckedCell::KnownEmpty
+        } else if self.islands.get(index) {
+            CheckedCell::Island
+        } else if self.mines.get(index) {
+            CheckedCell::Mine
+        } else {
+            CheckedCell::Unchecked
+        }
+    }
+
+    pub fn mark_sunk(&mut self, ship: &Ship) {
+        for (x, y) in ship.cells() {
+            let index = y * self.width + x;
+            self.hits.remove(index);
+            self.kills.insert(index);
+        }
+        for (x, y) in ship.cells() {
+            self.mark_known_empty_around(x, y, self.touching.empty_around_kill());
+        }
+    }
+
+    fn mark_known_empty_around(&mut self, x: usize, y: usize, offsets: &[(isize, isize)]) {
+        let (width, height) = (self.width as isize, self.height as isize);
+
+        for (dx, dy) in offsets {
+            let (x, y) = (x as isize + dx, y as isize + dy);
+            if x < 0 || y < 0 || x >= width || y >= height {
+                continue;
+            }
+
+            let index = (y * width + x) as usize;
+            let known = [&self.misses, &self.hits, &self.kills, &self.islands, &self.mines];
+            if !known.iter().any(|set| set.get(index)) {
+                self.known_empty.insert(index);
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! The way cells are written, shared by shots and ships. A cell is a row
+//! label and a column number, in either order, optionally separated by a
+//! `:` or spaces: `A5`, `a5`, `5A`, `A 5` and `A:5` are all the same cell.
+//! Rows after `Z` go on like spreadsheet columns: `AA`, `AB`, ...
+//!
+//! Whether the cell is on the board is up to the game, which knows its size.
+//! Cells past the largest field any game can have aren't read at all, so
+//! nothing further on has to worry about them overflowing.
+
+use std::fmt::Display;
+
+use super::{game_constants::MAX_FIELD_SIZE, shot::Shot};
+use crate::utilities::conversions;
+
+/// Characters that are easy to type instead of a digit.
+const LOOKALIKES: [(char, char); 5] = [('O', '0'), ('o', '0'), ('l', '1'), ('I', '1'), ('|', '1')];
+
+/// Reads a cell, or explains why it can't be one.
+pub fn parse(text: &str) -> Result<Shot, CoordinateError> {
+    let text = text.trim();
+
+    read(text).map_err(|reason| CoordinateError {
+        input: text.to_string(),
+        reason,
+        suggestion: suggest(text),
+    })
+}
+
+fn read(text: &str) -> Result<Shot, CoordinateErrorReason> {
+    if text.is_empty() {
+        return Err(CoordinateErrorReason::Empty);
+    }
+
+    let mut row: Option<&str> = None;
+    let mut column: Option<&str> = None;
+    let mut separated = false;
+    let mut rest = text;
+
+    while let Some(next) = rest.chars().next() {
+        let run = |is_part: fn(&char) -> bool| rest.find(|c: char| !is_part(&c)).unwrap_or(rest.len());
+
+        if next.is_ascii_alphabetic() && row.is_none() {
+            let end = run(char::is_ascii_alphabetic);
+            row = Some(&rest[..end]);
+            rest = &rest[end..];
+        } else if next.is_ascii_digit() && column.is_none() {
+            let end = run(char::is_ascii_digit);
+            column = Some(&rest[..end]);
+            rest = &rest[end..];
+        } else if (next == ':' || next.is_whitespace()) && row.is_some() != column.is_some() {
+            // Only one `:` fits between the row and the column.
+            if next == ':' && separated {
+                return Err(CoordinateErrorReason::Unexpected(next));
+            }
+            separated |= next == ':';
+            rest = &rest[next.len_utf8()..];
+        } else {
+            return Err(CoordinateErrorReason::Unexpected(next));
+        }
+    }
+
+    let row = row.ok_or(CoordinateErrorReason::MissingRow)?;
+    let column = column.ok_or(CoordinateErrorReason::MissingColumn)?;
+
+    let y = conversions::label_to_usize(row).ok_or(CoordinateErrorReason::TooLarge)?;
+    let x = match column.parse::<usize>() {
+        Ok(0) => return Err(CoordinateErrorReason::ZeroColumn),
+        Ok(column) => column - 1,
+        Err(_) => return Err(CoordinateErrorReason::TooLarge),
+    };
+    if x >= MAX_FIELD_SIZE || y >= MAX_FIELD_SIZE {
+        return Err(CoordinateErrorReason::TooLarge);
+    }
+
+    Ok(Shot { x, y })
+}
+
+/// A cell close to `text` that can be read: with stray punctuation left
+/// out, or with letters that look like digits read as digits. Only
+/// single-letter rows are suggested, anything else is too far a guess.
+fn suggest(text: &str) -> Option<Shot> {
+    let without_punctuation: String = text.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == ':' || c.is_whitespace())
+        .collect::<String>()
+        .trim_matches(|c: char| c == ':' || c.is_whitespace())
+        .to_string();
+    // The first character is left alone, since it's usually the row.
+    let digits = |text: &str| -> String {
+        text.chars()
+            .enumerate()
+            .map(|(index, c)| match LOOKALIKES.iter().find(|(lookalike, _)| *lookalike == c) {
+                Some(&(_, digit)) if index > 0 => digit,
+                _ => c,
+            })
+            .collect()
+    };
+
+    [without_punctuation.clone(), digits(text), digits(&without_punctuation)]
+        .iter()
+        .filter_map(|candidate| read(candidate).ok())
+        .find(|cell| cell.y < 26)
+}
+
+#[derive(Debug)]
+pub struct CoordinateError {
+    pub input: String,
+    pub reason: CoordinateErrorReason,
+    /// A close cell that can be read, if there is one.
+    pub suggestion: Option<Shot>,
+}
+
+#[derive(Debug)]
+pub enum CoordinateErrorReason {
+    Empty,
+    MissingRow,
+    MissingColumn,
+    ZeroColumn,
+    TooLarge,
+    Unexpected(char),
+}
+
+impl Display for CoordinateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.input.is_empty() {
+            write!(f, "no cell given: write it like 'A:5'")?;
+        } else {
+            write!(f, "can't read '{}' as a cell: {}", self.input, self.reason)?;
+        }
+        if let Some(suggestion) = self.suggestion {
+            write!(f, " (did you mean '{suggestion}'?)")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for CoordinateErrorReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CoordinateErrorReason::Empty => write!(f, "it's empty"),
+            CoordinateErrorReason::MissingRow => write!(f, "the row letter is missing"),
+            CoordinateErrorReason::MissingColumn => write!(f, "the column number is missing"),
+            CoordinateErrorReason::ZeroColumn => write!(f, "columns start at 1"),
+            CoordinateErrorReason::TooLarge => write!(f, "that's far outside of any field"),
+            CoordinateErrorReason::Unexpected(c) => write!(f, "didn't expect '{c}' there"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(text: &str) -> CoordinateErrorReason {
+        parse(text).expect_err("the cell can't be read").reason
+    }
+
+    #[test]
+    fn reads_cells_on_the_largest_field() {
+        let cell = parse("A1").unwrap();
+        assert_eq!((cell.x, cell.y), (0, 0));
+        let cell = parse(&format!("CV:{MAX_FIELD_SIZE}")).unwrap();
+        assert_eq!((cell.x, cell.y), (MAX_FIELD_SIZE - 1, MAX_FIELD_SIZE - 1));
+    }
+
+    #[test]
+    fn refuses_huge_cells() {
+        assert!(matches!(reason("A18446744073709551615"), CoordinateErrorReason::TooLarge));
+        assert!(matches!(reason("A18446744073709551616"), CoordinateErrorReason::TooLarge));
+        assert!(matches!(reason(&format!("A{}", MAX_FIELD_SIZE + 1)), CoordinateErrorReason::TooLarge));
+        assert!(matches!(reason("CW1"), CoordinateErrorReason::TooLarge));
+        assert!(matches!(reason("ZZZZZZZZZZZZZZZZ1"), CoordinateErrorReason::TooLarge));
+    }
+
+    #[test]
+    fn refuses_column_zero() {
+        assert!(matches!(reason("A0"), CoordinateErrorReason::ZeroColumn));
+        assert!(matches!(reason("0A"), CoordinateErrorReason::ZeroColumn));
+        assert!(matches!(reason("A:000"), CoordinateErrorReason::ZeroColumn));
+    }
+}
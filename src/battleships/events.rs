@@ -0,0 +1,108 @@
+use std::{fmt::Display, io::Write};
+
+use super::{
+    game::{Side, Command},
+    ship::Ship,
+    shot::Shot,
+    weapons::{Special, SonarReading},
+};
+
+/// Something that happened in a game.
+#[derive(Debug)]
+#[derive(Clone)]
+pub enum Event {
+    ShipPlaced(Side, Ship),
+    ShipRemoved(Side, Ship),
+    ShipMoved { side: Side, from: Ship, to: Ship },
+    /// This side took every placed ship off the field.
+    FleetCleared(Side),
+    MinePlaced(Side, Shot),
+    MineRemoved(Side, Shot),
+    /// This side has confirmed its whole fleet.
+    FleetPlaced(Side),
+    ShotFired { side: Side, shot: Shot },
+    Miss { side: Side, shot: Shot },
+    Hit { side: Side, shot: Shot },
+    /// `side` sunk the enemy ship made of `cells` (as `(x, y)`).
+    ShipSunk { side: Side, shot: Shot, cells: Vec<(usize, usize)> },
+    /// `side` used up a special attack. What it hit follows as `Miss`,
+    /// `Hit` and `ShipSunk` events for every cell it fired at.
+    SpecialUsed { side: Side, special: Special },
+    SonarPing { side: Side, reading: SonarReading },
+    /// A shot of `side` set off an enemy mine, which damaged their own
+    /// ship at `damaged`, if they had any left.
+    MineHit { side: Side, shot: Shot, damaged: Option<Shot>, sunk: bool },
+    TurnChanged(Side),
+    GameOver { winner: Side },
+    /// This command was taken back.
+    Undone(Command),
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::ShipPlaced(side, ship) => write!(f, "{side} placed {} at {}", ship.shape.describe(), origin(ship)),
+            Event::ShipRemoved(side, ship) => write!(f, "{side} removed the ship at {}", origin(ship)),
+            Event::ShipMoved { side, from, to } => {
+                write!(f, "{side} moved the ship at {} to {}", origin(from), origin(to))
+            }
+            Event::FleetCleared(side) => write!(f, "{side} took every ship off the field"),
+            Event::MinePlaced(side, mine) => write!(f, "{side} hid a mine at {mine}"),
+            Event::MineRemoved(side, mine) => write!(f, "{side} removed the mine at {mine}"),
+            Event::FleetPlaced(side) => write!(f, "{side} placed the whole fleet"),
+            Event::ShotFired { side, shot } => write!(f, "{side} fired at {shot}"),
+            Event::Miss { side, .. } => write!(f, "{side} missed"),
+            Event::Hit { side, .. } => write!(f, "{side} hit a ship"),
+            Event::ShipSunk { side, cells, .. } => {
+                let cells: Vec<String> = cells.iter()
+                    .map(|&(x, y)| Shot { x, y }.to_string())
+                    .collect();
+                write!(f, "{side} sunk the ship at {}", cells.join(" "))
+            }
+            Event::SpecialUsed { side, special } => write!(f, "{side} used {special}"),
+            Event::SonarPing { side, reading } => write!(f, "{side}'s sonar found {reading}"),
+            Event::MineHit { side, damaged: None, .. } => write!(f, "{side} hit a mine"),
+            Event::MineHit { side, damaged: Some(damaged), sunk, .. } => {
+                let outcome = if *sunk { "sunk" } else { "hit" };
+                write!(f, "{side} hit a mine, which {outcome} their own ship at {damaged}")
+            }
+            Event::TurnChanged(side) => write!(f, "{side}'s turn"),
+            Event::GameOver { winner } => write!(f, "{winner} won the game"),
+            Event::Undone(Command::Shoot(side, shot)) => write!(f, "{side} took back the shot at {shot}"),
+            Event::Undone(Command::UseSpecial(side, special)) => write!(f, "{side} took back the {special}"),
+            Event::Undone(Command::PlaceShip(side, _) | Command::RemoveShip(side, _)
+                | Command::MoveShip { side, .. } | Command::ClearFleet(side) | Command::PlaceMine(side, _)
+                | Command::RemoveMine(side, _) | Command::ConfirmFleet(side)) => {
+                write!(f, "{side} took back a change to the fleet")
+            }
+        }
+    }
+}
+
+/// Where the ship starts, as a shot at that cell would be written.
+fn origin(ship: &Ship) -> Shot {
+    Shot { x: ship.x, y: ship.y }
+}
+
+/// Gets told about every event of the game it is subscribed to.
+pub trait Observer {
+    fn notify(&mut self, event: &Event);
+}
+
+/// Writes every event as a line of text.
+pub struct EventLog<W: Write> {
+    output: W,
+}
+
+impl<W: Write> EventLog<W> {
+    pub fn new(output: W) -> EventLog<W> {
+        EventLog { output }
+    }
+}
+
+impl<W: Write> Observer for EventLog<W> {
+    fn notify(&mut self, event: &Event) {
+        // A broken log must never break the game itself.
+        let _ = writeln!(self.output, "{event}");
+    }
+}
@@ -0,0 +1,349 @@
+//! Whether a fleet fits on a field at all, decided before the game starts
+//! so that neither the players nor the random layouts search forever.
+//!
+//! The search goes over the cells row by row, and at every cell either
+//! starts one of the remaining ships there or leaves the cell empty. A
+//! ship starts at its first cell in reading order, which no other ship
+//! can share, even where the box around it has an empty corner. Ships of
+//! the same shape are interchangeable, so only one of them is tried at
+//! every cell, and dead ends are remembered by what is left to place and
+//! which of the cells still ahead are blocked.
+
+use std::{collections::HashSet, fmt::Display};
+
+use super::{
+    bitboard::BitSet,
+    rules::{Rules, FieldSize, TouchingPolicy},
+    shape::Shape,
+    ship::{Ship, Rotation},
+};
+
+/// How many cells the search may look at before giving up.
+const SEARCH_LIMIT: usize = 2_000_000;
+/// How many dead ends are remembered at most.
+const MEMO_LIMIT: usize = 500_000;
+
+/// A legal layout of the whole fleet on the field of `rules`, or why
+/// there can't be one.
+pub fn solve(rules: &Rules) -> Result<Vec<Ship>, FeasibilityError> {
+    let FieldSize { width, height } = rules.size;
+
+    if rules.fleet.contains(&Shape::Line(0)) {
+        return Err(FeasibilityError::EmptyShip);
+    }
+    if let Some(&shape) = rules.fleet.iter().find(|shape| !fits_on(**shape, rules.size)) {
+        return Err(FeasibilityError::ShipTooBig(shape));
+    }
+    let longest = rules.fleet.iter().map(|shape| shape.extent()).max().unwrap_or(0);
+    // Mines go on any open water, even right next to a ship.
+    let ship_cells: usize = rules.fleet.iter().map(|shape| shape.size()).sum();
+    let water = rules.size.cells().saturating_sub(rules.islands.len() + ship_cells);
+    if rules.mines > water {
+        return Err(FeasibilityError::TooManyMines { mines: rules.mines, water });
+    }
+    let needed = rules.fleet.iter().map(|&shape| footprint(shape, rules.touching)).sum();
+    let (extra_columns, extra_rows) = margins(rules.touching);
+    let available = (width + extra_columns) * (height + extra_rows);
+    if needed > available {
+        return Err(FeasibilityError::FleetTooLarge { needed, available });
+    }
+
+    let mut shapes: Vec<Shape> = Vec::new();
+    for &shape in &rules.fleet {
+        if !shapes.contains(&shape) {
+            shapes.push(shape);
+        }
+    }
+    shapes.sort_by_key(|shape| std::cmp::Reverse(shape.size()));
+    let counts = shapes.iter()
+        .map(|shape| rules.fleet.iter().filter(|other| *other == shape).count())
+        .collect();
+
+    let mut search = Search {
+        size: rules.size,
+        touching: rules.touching,
+        window: (longest + 1) * width,
+        shapes,
+        counts,
+        placed: Vec::new(),
+        dead_ends: HashSet::new(),
+        visited: 0,
+    };
+    let mut islands = BitSet::new(rules.size.cells());
+    for &(x, y) in &rules.islands {
+        islands.insert(y * width + x);
+    }
+    if search.run(&islands, 0, needed)? {
+        Ok(search.placed)
+    } else {
+        Err(FeasibilityError::NoLayout)
+    }
+}
+
+/// Whether `shape` fits on the field lying at least one way.
+fn fits_on(shape: Shape, size: FieldSize) -> bool {
+    shape.orientations().into_iter().any(|(rotation, mirrored)| {
+        shape.cells(rotation, mirrored).all(|(x, y)| x < size.width && y < size.height)
+    })
+}
+
+/// Cells a ship takes together with some of the cells next to it that
+/// must stay empty, on a field grown by `margins`, however it's turned.
+/// No two ships' footprints overlap there.
+fn footprint(shape: Shape, touching: TouchingPolicy) -> usize {
+    let Shape::Line(length) = shape else {
+        return shape.orientations().into_iter()
+            .map(|(rotation, mirrored)| shaped_footprint(shape, rotation, mirrored, touching))
+            .min()
+            .unwrap_or(0);
+    };
+    match touching {
+        // The ship and the cells right of and below it.
+        TouchingPolicy::NoTouch => 2 * (length + 1),
+        // The ship and the cell right of its first one.
+        TouchingPolicy::CornersOnly => length + 1,
+        TouchingPolicy::Anything => length,
+    }
+}
+
+/// `footprint` of a shaped ship lying one way: its cells moved by every
+/// offset that `margins` grows the field by.
+fn shaped_footprint(shape: Shape, rotation: Rotation, mirrored: bool, touching: TouchingPolicy) -> usize {
+    let (extra_columns, extra_rows) = margins(touching);
+    let mut cells: Vec<(usize, usize)> = shape.cells(rotation, mirrored)
+        .flat_map(|(x, y)| {
+            (0..=extra_rows).flat_map(move |dy| (0..=extra_columns).map(move |dx| (x + dx, y + dy)))
+        })
+        .collect();
+    cells.sort_unstable();
+    cells.dedup();
+    cells.len()
+}
+
+/// How many columns and rows the footprints can stick out of the field.
+fn margins(touching: TouchingPolicy) -> (usize, usize) {
+    match touching {
+        TouchingPolicy::NoTouch => (1, 1),
+        TouchingPolicy::CornersOnly => (1, 0),
+        TouchingPolicy::Anything => (0, 0),
+    }
+}
+
+/// What is left to do when the search reaches a cell: the ships still to
+/// place and the blocked cells from there on.
+#[derive(PartialEq, Eq, Hash)]
+struct State {
+    cell: usize,
+    counts: Vec<usize>,
+    blocked: Vec<u64>,
+}
+
+struct Search {
+    size: FieldSize,
+    touching: TouchingPolicy,
+    /// How far past the current cell earlier ships can block cells.
+    window: usize,
+    /// Every distinct ship shape, largest first.
+    shapes: Vec<Shape>,
+    /// How many ships of every shape are left to place.
+    counts: Vec<usize>,
+    placed: Vec<Ship>,
+    dead_ends: HashSet<State>,
+    visited: usize,
+}
+
+impl Search {
+    /// Places the remaining ships, starting at the cell `start` or later,
+    /// on cells that aren't `blocked`. `needed` is the sum of their
+    /// footprints.
+    fn run(&mut self, blocked: &BitSet, start: usize, needed: usize) -> Result<bool, FeasibilityError> {
+        if needed == 0 {
+            return Ok(true);
+        }
+        let FieldSize { width, height } = self.size;
+        let ship_cells: usize = self.shapes.iter().zip(&self.counts).map(|(shape, count)| shape.size() * count).sum();
+        let mut passed = Vec::new();
+
+        for cell in start..width * height {
+            self.visited += 1;
+            if self.visited > SEARCH_LIMIT {
+                return Err(FeasibilityError::Undecided);
+            }
+
+            // The footprints of the ships still to come all start at this
+            // cell or later, so they must fit into what is left.
+            let (x, y) = (cell % width, cell / width);
+            let (extra_columns, extra_rows) = margins(self.touching);
+            if needed > (width + extra_columns) * (height + extra_rows - y) - x {
+                break;
+            }
+
+            let state = State {
+                cell,
+                counts: self.counts.clone(),
+                blocked: blocked.slice(cell, (cell + self.window).min(width * height)),
+            };
+
+            // Nor can the ships take more cells than are still free.
+            let blocked_ahead: usize = state.blocked.iter().map(|word| word.count_ones() as usize).sum();
+            if ship_cells > width * height - cell - blocked_ahead {
+                break;
+            }
+            if self.dead_ends.contains(&state) {
+                break;
+            }
+            passed.push(state);
+
+            for which in 0..self.shapes.len() {
+                if self.counts[which] == 0 {
+                    continue;
+                }
+                let shape = self.shapes[which];
+
+                for (rotation, mirrored) in shape.orientations() {
+                    let first = shape.cells(rotation, mirrored).min_by_key(|&(x, y)| (y, x)).unwrap_or((0, 0));
+                    let (Some(left), Some(top)) = (x.checked_sub(first.0), y.checked_sub(first.1)) else {
+                        continue;
+                    };
+                    let ship = Ship { shape, x: left, y: top, rotation, mirrored };
+                    if !self.fits(blocked, &ship) {
+                        continue;
+                    }
+
+                    let mut next = blocked.clone();
+                    self.block(&mut next, &ship);
+                    self.counts[which] -= 1;
+                    self.placed.push(ship);
+
+                    if self.run(&next, cell + 1, needed - footprint(shape, self.touching))? {
+                        return Ok(true);
+                    }
+
+                    self.placed.pop();
+                    self.counts[which] += 1;
+                }
+            }
+        }
+
+        for state in passed {
+            if self.dead_ends.len() >= MEMO_LIMIT {
+                break;
+            }
+            self.dead_ends.insert(state);
+        }
+        Ok(false)
+    }
+
+    fn fits(&self, blocked: &BitSet, ship: &Ship) -> bool {
+        let FieldSize { width, height } = self.size;
+        ship.cells().all(|(x, y)| x < width && y < height && !blocked.get(y * width + x))
+    }
+
+    /// Blocks the cells of `ship` and the ones around it that the touching
+    /// policy keeps free.
+    fn block(&self, blocked: &mut BitSet, ship: &Ship) {
+        let FieldSize { width, height } = self.size;
+
+        for (x, y) in ship.cells() {
+            blocked.insert(y * width + x);
+            for &(dx, dy) in self.touching.empty_around_kill() {
+                let (nx, ny) = (x as isize + dx, y as isize + dy);
+                if nx >= 0 && ny >= 0 && (nx as usize) < width && (ny as usize) < height {
+                    blocked.insert(ny as usize * width + nx as usize);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FeasibilityError {
+    EmptyShip,
+    ShipTooBig(Shape),
+    /// The ships and the space around them take more room than there is.
+    FleetTooLarge { needed: usize, available: usize },
+    /// There isn't enough open water left for every mine.
+    TooManyMines { mines: usize, water: usize },
+    /// Every way of placing the fleet was tried.
+    NoLayout,
+    /// The search took too long to tell either way.
+    Undecided,
+}
+
+impl Display for FeasibilityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FeasibilityError::EmptyShip => write!(f, "ships must be at least one cell long"),
+            FeasibilityError::ShipTooBig(shape) => write!(f, "{} doesn't fit on the field", shape.describe()),
+            FeasibilityError::FleetTooLarge { needed, available } => write!(
+                f,
+                "the fleet is too large: with the space around them the ships need {needed} cells, but there are only {available}"
+            ),
+            FeasibilityError::TooManyMines { mines, water } => {
+                write!(f, "{mines} mines don't fit into the {water} cells the ships and islands leave")
+            }
+            FeasibilityError::NoLayout => write!(f, "there's no way to place the whole fleet"),
+            FeasibilityError::Undecided => write!(f, "couldn't work out in time whether the fleet fits"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battleships::game::{Game, Phase, Side};
+
+    fn rules(width: usize, height: usize, touching: TouchingPolicy, fleet: Vec<Shape>) -> Rules {
+        Rules { size: FieldSize { width, height }, touching, fleet, ..Rules::default() }
+    }
+
+    /// Solves `rules` and checks the game takes the layout it found.
+    fn assert_fits(rules: Rules) {
+        let layout = solve(&rules).unwrap();
+        let mut game = Game::new(rules);
+        game.place_fleet(Side::First, &layout).unwrap();
+        assert!(game.is_fleet_confirmed(Side::First));
+        assert_eq!(game.phase(), Phase::Placement);
+    }
+
+    #[test]
+    fn fills_a_field_with_ships_that_may_touch() {
+        assert_fits(rules(10, 10, TouchingPolicy::Anything, vec![Shape::Line(10); 10]));
+        assert!(matches!(
+            solve(&rules(10, 10, TouchingPolicy::Anything, vec![Shape::Line(10); 11])),
+            Err(FeasibilityError::FleetTooLarge { needed: 110, available: 100 }),
+        ));
+    }
+
+    #[test]
+    fn leaves_room_between_ships_that_may_not_touch() {
+        assert_fits(rules(10, 10, TouchingPolicy::NoTouch, vec![Shape::Line(1); 25]));
+        // Small enough by area, but there's no way to space them out.
+        assert!(matches!(solve(&rules(10, 10, TouchingPolicy::NoTouch, vec![Shape::Line(1); 26])), Err(FeasibilityError::NoLayout)));
+    }
+
+    #[test]
+    fn lets_ships_meet_at_corners_only_when_allowed() {
+        assert_fits(rules(5, 1, TouchingPolicy::CornersOnly, vec![Shape::Line(2); 2]));
+        assert!(matches!(
+            solve(&rules(4, 1, TouchingPolicy::CornersOnly, vec![Shape::Line(2); 2])),
+            Err(FeasibilityError::FleetTooLarge { .. }),
+        ));
+    }
+
+    #[test]
+    fn leaves_open_water_for_the_mines() {
+        let fleet = vec![Shape::Line(10); 9];
+        assert!(solve(&Rules { mines: 10, ..rules(10, 10, TouchingPolicy::Anything, fleet.clone()) }).is_ok());
+        assert!(matches!(
+            solve(&Rules { mines: 11, ..rules(10, 10, TouchingPolicy::Anything, fleet) }),
+            Err(FeasibilityError::TooManyMines { mines: 11, water: 10 }),
+        ));
+    }
+
+    #[test]
+    fn refuses_ships_that_fit_on_no_field() {
+        assert!(matches!(solve(&rules(10, 10, TouchingPolicy::Anything, vec![Shape::Line(11)])), Err(FeasibilityError::ShipTooBig(_))));
+        assert!(matches!(solve(&rules(10, 10, TouchingPolicy::Anything, vec![Shape::Line(0)])), Err(FeasibilityError::EmptyShip)));
+    }
+}
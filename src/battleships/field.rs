@@ -1,7 +1,9 @@
 use std::fmt::Display;
 
-use super::ship::{Ship, Rotation};
-use super::game_constants::{FIELD_SIZE, FIRST_LETTER, LAST_LETTER};
+use super::shape::Shape;
+use super::ship::Ship;
+use super::rules::TouchingPolicy;
+use super::weapons::SonarReading;
 
 use crate::utilities::{
     conversions
@@ -16,6 +18,12 @@ pub enum FieldCell {
     EnemyMiss,
     Hit,
     Dead,
+    /// Land, where nothing can be placed or shot.
+    Island,
+    /// A hidden mine, waiting for the enemy to shoot at it.
+    Mine,
+    /// A mine the enemy shot at, which went off.
+    Crater,
 }
 
 #[derive(Debug)]
@@ -26,70 +34,146 @@ pub enum CheckedCell {
     Miss,
     Hit,
     Kill,
+    /// Never shot at, but the touching rules say no ship can be here.
+    KnownEmpty,
+    Island,
+    /// A shot went off on an enemy mine here.
+    Mine,
 }
 
 pub struct CheckField {
     field: Vec<Vec<CheckedCell>>,
-    size: usize,
+    /// Ships sunk so far. Touching ships can't be told apart by their
+    /// cells alone, so these are kept as they were reported.
+    sunk: Vec<Ship>,
+    /// What every sonar ping found, in order.
+    sonar: Vec<SonarReading>,
+    width: usize,
+    height: usize,
+    touching: TouchingPolicy,
+    /// Whether the ships can bend, so that the cells diagonal to a hit may
+    /// belong to the same ship.
+    bent_ships: bool,
 }
 
 impl CheckField {
-    pub fn new(size: usize) -> CheckField {
-        let field = vec![vec![CheckedCell::Unchecked; size]; size];
+    pub fn new(width: usize, height: usize) -> CheckField {
+        CheckField::with_touching(width, height, TouchingPolicy::NoTouch)
+    }
+
+    pub fn with_touching(width: usize, height: usize, touching: TouchingPolicy) -> CheckField {
+        let field = vec![vec![CheckedCell::Unchecked; height]; width];
 
         CheckField {
             field,
-            size
+            sunk: Vec::new(),
+            sonar: Vec::new(),
+            width,
+            height,
+            touching,
+            bent_ships: false,
         }
     }
 
+    /// Stops a hit from ruling out the cells diagonal to it, for fleets
+    /// with shaped ships.
+    pub fn allow_bent_ships(&mut self) {
+        self.bent_ships = true;
+    }
+
+    /// Marks a single cell. Sunk ships go through `mark_sunk`, which also
+    /// knows which cells were the ship.
     pub fn mark(&mut self, x: usize, y: usize, marker: CheckedCell) {
-        if marker == CheckedCell::Kill {
-            self.mark_kill(x, y);
-        } else {
-            self.field[x][y] = marker;
+        match marker {
+            CheckedCell::Hit => {
+                self.field[x][y] = marker;
+                if !self.bent_ships {
+                    self.mark_known_empty_around(x, y, self.touching.empty_around_hit());
+                }
+            }
+            _ => self.field[x][y] = marker,
         }
     }
 
     pub fn at(&self, x: usize, y: usize) -> CheckedCell {
-        assert!(x < self.size && y < self.size);
+        assert!(x < self.width && y < self.height);
         self.field[x][y]
     }
 
-    fn mark_kill(&mut self, x: usize, y: usize) {
-        self.field[x][y] = CheckedCell::Hit;
-        mark_kill_recursive(self, x as isize, y as isize);
+    pub fn width(&self) -> usize {
+        self.width
+    }
 
-        fn mark_kill_recursive(me: &mut CheckField, x: isize, y: isize) {
-            if x < 0 || y < 0 || x >= me.size as isize || y >= me.size as isize {
-                return;
-            }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn touching(&self) -> TouchingPolicy {
+        self.touching
+    }
+
+    /// Every ship sunk so far, in the order they went down.
+    pub fn sunk_ships(&self) -> &[Ship] {
+        &self.sunk
+    }
+
+    pub fn sonar_readings(&self) -> &[SonarReading] {
+        &self.sonar
+    }
 
-            let cell = me.at(x as usize, y as usize);
+    pub fn add_sonar_reading(&mut self, reading: SonarReading) {
+        self.sonar.push(reading);
+    }
 
-            if cell == CheckedCell::Hit{
-                me.field[x as usize][y as usize] = CheckedCell::Kill;
-                mark_kill_recursive(me, x - 1, y);
-                mark_kill_recursive(me, x + 1, y);
-                mark_kill_recursive(me, x, y + 1);
-                mark_kill_recursive(me, x, y - 1);
+    pub fn mark_sunk(&mut self, ship: &Ship) {
+        for (x, y) in ship.cells() {
+            self.field[x][y] = CheckedCell::Kill;
+        }
+        for (x, y) in ship.cells() {
+            self.mark_known_empty_around(x, y, self.touching.empty_around_kill());
+        }
+        self.sunk.push(*ship);
+    }
+
+    fn mark_known_empty_around(&mut self, x: usize, y: usize, offsets: &[(isize, isize)]) {
+        for (dx, dy) in offsets {
+            let (x, y) = (x as isize + dx, y as isize + dy);
+            if self.is_out_of_bounds(x, y) {
+                continue;
+            }
+
+            let cell = &mut self.field[x as usize][y as usize];
+            if *cell == CheckedCell::Unchecked {
+                *cell = CheckedCell::KnownEmpty;
             }
         }
     }
+
+    fn is_out_of_bounds(&self, x: isize, y: isize) -> bool {
+        x < 0 || y < 0 || x >= self.width as isize || y >= self.height as isize
+    }
 }
 
 pub struct PlayerField {
     field: Vec<Vec<FieldCell>>,
-    size: usize,
+    width: usize,
+    height: usize,
+    touching: TouchingPolicy,
 }
 
 impl PlayerField {
-    pub fn new(size: usize) -> PlayerField {
-        let field = vec![vec![FieldCell::Empty; size]; size];
+    pub fn new(width: usize, height: usize) -> PlayerField {
+        PlayerField::with_touching(width, height, TouchingPolicy::NoTouch)
+    }
+
+    pub fn with_touching(width: usize, height: usize, touching: TouchingPolicy) -> PlayerField {
+        let field = vec![vec![FieldCell::Empty; height]; width];
 
         PlayerField {
             field,
-            size,
+            width,
+            height,
+            touching,
         }
     }
 
@@ -98,51 +182,27 @@ impl PlayerField {
         self.field[x][y]
     }
 
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
     #[inline]
     pub fn set(&mut self, x: usize, y: usize, value: FieldCell) {
         self.field[x][y] = value;
     }
 
-    pub fn will_ship_survive_after_shot(&self, x: usize, y: usize) -> bool {
-        let x = x as isize;
-        let y = y as isize;
-
-        return walk(self, x, y, 1, 0) ||
-            walk(self, x, y, -1, 0) ||
-            walk(self, x, y, 0, 1) ||
-            walk(self, x, y, 0, -1);
-
-
-        fn walk(me: &PlayerField, x: isize, y: isize, xdir: isize, ydir: isize) -> bool {
-            if me.is_out_of_bounds(x + xdir, y + ydir) {
-                return false;
-            }
-
-            match me.at((x + xdir) as usize, (y + ydir) as usize) {
-                FieldCell::Ship => true,
-                FieldCell::Hit => walk(me, x + xdir, y + ydir, xdir, ydir),
-                _ => false,
-            }
-        }
+    /// Whether `ship` still has cells that weren't hit, once `(x, y)` is.
+    pub fn will_ship_survive_after_shot(&self, ship: &Ship, x: usize, y: usize) -> bool {
+        ship.cells().any(|cell| cell != (x, y) && self.at(cell.0, cell.1) == FieldCell::Ship)
     }
 
-    pub fn mark_dead(&mut self, x: usize, y: usize) {
-        mark_dead_recursive(self, x as isize, y as isize);
-
-        fn mark_dead_recursive(me: &mut PlayerField, x: isize, y: isize) {
-            if me.is_out_of_bounds(x, y) {
-                return;
-            }
-
-            let cell = me.at(x as usize, y as usize);
-
-            if cell == FieldCell::Ship || cell == FieldCell::Hit {
-                me.field[x as usize][y as usize] = FieldCell::Dead;
-                mark_dead_recursive(me, x - 1, y);
-                mark_dead_recursive(me, x + 1, y);
-                mark_dead_recursive(me, x, y + 1);
-                mark_dead_recursive(me, x, y - 1);
-            }
+    pub fn mark_dead(&mut self, ship: &Ship) {
+        for (x, y) in ship.cells() {
+            self.set(x, y, FieldCell::Dead);
         }
     }
 
@@ -158,102 +218,96 @@ impl PlayerField {
         self.at(x, y) == FieldCell::Ship
     }
 
+    pub fn has_ships_left(&self) -> bool {
+        self.field.iter().flatten().any(|cell| *cell == FieldCell::Ship)
+    }
+
     pub fn place_ship(&mut self, ship: &Ship) {
         debug_assert!(self.can_place(ship).is_ok());
 
-        match ship.rotation {
-            Rotation::Horizontal => for i in 0..ship.length {
-                self.field[ship.x + i][ship.y] = FieldCell::Ship 
-            },
-            Rotation::Vertical => for i in 0..ship.length { 
-                self.field[ship.x][ship.y + i] = FieldCell::Ship 
-            },    
+        for (x, y) in ship.cells() {
+            self.field[x][y] = FieldCell::Ship;
+        }
+    }
+
+    /// Clears every cell of a placed `ship`.
+    pub fn remove_ship(&mut self, ship: &Ship) {
+        for (x, y) in ship.cells() {
+            debug_assert!(self.field[x][y] == FieldCell::Ship);
+            self.field[x][y] = FieldCell::Empty;
         }
     }
 
     pub fn can_place(&self, ship: &Ship) -> Result<(), ShipPlacementError> {
-        match ship.rotation {
-            Rotation::Horizontal => for i in 0..ship.length {
-                if let Err(error) = self.can_place_on(ship.x + i, ship.y) {
-                    return Err(error);
-                }
-            },
-            Rotation::Vertical => for i in 0..ship.length { 
-                if let Err(error) = self.can_place_on(ship.x, ship.y + i) {
-                    return Err(error);
-                }
-            },    
+        for (x, y) in ship.cells() {
+            self.can_place_on(x, y)?;
         }
 
         Ok(())
     }
 
     fn can_place_on(&self, x: usize, y: usize) -> Result<(), ShipPlacementError> {
-        if self.out_of_bounds(x) {
-            return Err(ShipPlacementError::OutOfBoundsX);
+        if x >= self.width {
+            return Err(ShipPlacementError::OutOfBoundsX(self.width));
         }
-        if self.out_of_bounds(y) {
-            return Err(ShipPlacementError::OutOfBoundsY);
+        if y >= self.height {
+            return Err(ShipPlacementError::OutOfBoundsY(self.height));
         }
 
-        for dy in -1..=1isize {
-            for dx in -1..=1isize {
-                let checked_x = if dx == -1 { sub_one(x) } else if dx == 0 { Some(x) } else { add_one(x, self.size) };
-                let checked_y = if dy == -1 { sub_one(y) } else if dy == 0 { Some(y) } else { add_one(y, self.size) };
-
-                let checked_x = match checked_x {
-                    Some(value) => value,
-                    None => { continue; }
-                };
-                let checked_y = match checked_y {
-                    Some(value) => value,
-                    None => { continue; }
-                };
-
-                if self.is_ship_on(checked_x, checked_y) {
-                    return Err(ShipPlacementError::TouchesWithShip(checked_x, checked_y));
-                }
+        match self.at(x, y) {
+            FieldCell::Island => return Err(ShipPlacementError::OnIsland(x, y)),
+            FieldCell::Mine => return Err(ShipPlacementError::OnMine(x, y)),
+            FieldCell::Ship => return Err(ShipPlacementError::TouchesWithShip(x, y)),
+            _ => {}
+        }
+        for (dx, dy) in self.touching.empty_around_kill() {
+            let (checked_x, checked_y) = (x as isize + dx, y as isize + dy);
+            if self.is_out_of_bounds(checked_x, checked_y) {
+                continue;
+            }
+            if self.is_ship_on(checked_x as usize, checked_y as usize) {
+                return Err(ShipPlacementError::TouchesWithShip(checked_x as usize, checked_y as usize));
             }
         }
 
         Ok(())
     }
 
-    fn out_of_bounds(&self, coordinate: usize) -> bool {
-        coordinate >= self.size
-    }
-
     fn is_out_of_bounds(&self, x: isize, y: isize) -> bool {
-        let size = self.size as isize;
-        x < 0 || y < 0 || x >= size || y >= size
+        x < 0 || y < 0 || x >= self.width as isize || y >= self.height as isize
     }
 }
 
-fn sub_one(coordinate: usize) -> Option<usize> {
-    if coordinate == 0 { None } else { Some(coordinate - 1) }
-}
-fn add_one(coordinate: usize, bounds: usize) -> Option<usize> {
-    if coordinate == bounds - 1 { None } else { Some(coordinate + 1) }
-}
-
+#[derive(Debug)]
 pub enum ShipPlacementError {
-    OutOfBoundsX,
-    OutOfBoundsY,
+    /// With the width or the height of the field.
+    OutOfBoundsX(usize),
+    OutOfBoundsY(usize),
     TouchesWithShip(usize, usize),
-    NoShipsOfLengthLeft(usize),
+    OnIsland(usize, usize),
+    OnMine(usize, usize),
+    /// Mines can't go on a ship.
+    OnShip(usize, usize),
+    NoShipsLeft(Shape),
 }
 
 impl Display for ShipPlacementError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ShipPlacementError::OutOfBoundsX => write!(f, "x was out of bounds! Should be in range 0..{FIELD_SIZE}"),
-            ShipPlacementError::OutOfBoundsY => write!(f, "y was out of bounds! Should be in range {FIRST_LETTER}..{LAST_LETTER}"),
-            ShipPlacementError::NoShipsOfLengthLeft(length) => write!(f, "no ships of length {length} left"),
+            ShipPlacementError::OutOfBoundsX(width) => write!(f, "x was out of bounds! Should be in range 1..{width}"),
+            ShipPlacementError::OutOfBoundsY(height) => {
+                write!(f, "y was out of bounds! Should be in range A..{}", conversions::usize_to_label(height - 1))
+            },
+            ShipPlacementError::NoShipsLeft(Shape::Line(length)) => write!(f, "no ships of length {length} left"),
+            ShipPlacementError::NoShipsLeft(shape) => write!(f, "no {shape}-shaped ships left"),
             ShipPlacementError::TouchesWithShip(other_x, other_y) => {
                 let other_x = other_x + 1;
-                let other_y = conversions::usize_to_coordinate(*other_y);
+                let other_y = conversions::usize_to_label(*other_y);
                 write!(f, "collision with another ship at {other_y}{other_x}")
             },
+            ShipPlacementError::OnIsland(x, y) => write!(f, "there's an island at {}{}", conversions::usize_to_label(*y), x + 1),
+            ShipPlacementError::OnMine(x, y) => write!(f, "there's a mine at {}{}", conversions::usize_to_label(*y), x + 1),
+            ShipPlacementError::OnShip(x, y) => write!(f, "there's a ship at {}{}", conversions::usize_to_label(*y), x + 1),
         }
     }
 }
@@ -0,0 +1,922 @@
+use std::fmt::Display;
+
+use super::{
+    player::{Player, Outcome},
+    shape::Shape,
+    ship::Ship,
+    shot::Shot,
+    field::{CheckedCell, FieldCell, ShipPlacementError},
+    rules::{Rules, FieldSize, Salvo, TurnOrder},
+    weapons::{self, Arsenal, Attack, Heading, Special, SonarReading, Weapon},
+    events::{Event, Observer},
+};
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Side {
+    First,
+    Second,
+}
+
+impl Side {
+    pub fn other(self) -> Side {
+        match self {
+            Side::First => Side::Second,
+            Side::Second => Side::First,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        match self {
+            Side::First => 0,
+            Side::Second => 1,
+        }
+    }
+}
+
+impl Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Side::First => write!(f, "Player 1"),
+            Side::Second => write!(f, "Player 2"),
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Phase {
+    /// Both players are still placing their fleets.
+    Placement,
+    /// It's this side's turn to shoot.
+    Shooting(Side),
+    /// The game is over and this side has won.
+    Finished(Side),
+}
+
+impl Phase {
+    /// The side on turn, if the game is in the shooting phase.
+    pub fn shooting_side(self) -> Option<Side> {
+        match self {
+            Phase::Shooting(side) => Some(side),
+            _ => None,
+        }
+    }
+}
+
+/// Everything a front end can ask the game to do.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub enum Command {
+    PlaceShip(Side, Ship),
+    /// Takes a placed ship off the field again.
+    RemoveShip(Side, Ship),
+    /// Puts a placed ship somewhere else, or turns it.
+    MoveShip { side: Side, from: Ship, to: Ship },
+    /// Takes every placed ship off the field at once.
+    ClearFleet(Side),
+    /// Hides a mine on the field of the side.
+    PlaceMine(Side, Shot),
+    RemoveMine(Side, Shot),
+    /// Locks the whole fleet in. The shooting starts once both sides did.
+    ConfirmFleet(Side),
+    Shoot(Side, Shot),
+    UseSpecial(Side, Special),
+}
+
+impl Command {
+    /// Who gave the command.
+    pub fn side(&self) -> Side {
+        match *self {
+            Command::PlaceShip(side, _)
+            | Command::RemoveShip(side, _)
+            | Command::MoveShip { side, .. }
+            | Command::ClearFleet(side)
+            | Command::PlaceMine(side, _)
+            | Command::RemoveMine(side, _)
+            | Command::ConfirmFleet(side)
+            | Command::Shoot(side, _)
+            | Command::UseSpecial(side, _) => side,
+        }
+    }
+
+    /// The command that fires `attack` for `side`.
+    pub fn attack(side: Side, attack: Attack) -> Command {
+        match attack {
+            Attack::Shot(shot) => Command::Shoot(side, shot),
+            Attack::Special(special) => Command::UseSpecial(side, special),
+        }
+    }
+
+    /// Whether the command fires at the enemy field.
+    pub fn is_attack(&self) -> bool {
+        matches!(self, Command::Shoot(..) | Command::UseSpecial(..))
+    }
+}
+
+/// What the side with a hidden fleet told about an attack at it.
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(Default)]
+pub struct Answer {
+    /// Every cell the attack fired at, in order, and what it did there.
+    /// A torpedo's cells end where it stopped.
+    pub cells: Vec<(Shot, Outcome)>,
+    /// How many ship cells a sonar ping found.
+    pub ship_cells: usize,
+    /// What every mine the side that told set off did to their own
+    /// fleet, in order: the cell it damaged and how, if they had any ship
+    /// left.
+    pub damage: Vec<Option<(Shot, Outcome)>>,
+}
+
+/// The rules of a match, without any printing or reading. Front ends feed
+/// it commands and get back the events they caused, which are also sent
+/// to every subscribed `Observer`.
+pub struct Game {
+    players: [Player; 2],
+    ships_to_place: [Vec<Shape>; 2],
+    confirmed: [bool; 2],
+    phase: Phase,
+    rules: Rules,
+    /// Every shot and special attack that hit the field so far, in order.
+    history: Vec<(Side, Attack)>,
+    /// Special attacks each side has left.
+    arsenals: [Arsenal; 2],
+    /// Shots of the volley being fired, which land once it is complete.
+    pending: Vec<Shot>,
+    /// Every command that changed the game, in order.
+    done: Vec<Command>,
+    /// Commands taken back with `undo`, the latest last.
+    undone: Vec<Command>,
+    undo_allowed: bool,
+    /// The side whose fleet is only known from what they answer, when
+    /// playing over the network.
+    hidden: Option<Side>,
+    observers: Vec<Box<dyn Observer>>,
+}
+
+fn new_players(rules: &Rules) -> [Player; 2] {
+    let FieldSize { width, height } = rules.size;
+    let mut players = [Player::with_touching(width, height, rules.touching), Player::with_touching(width, height, rules.touching)];
+    for player in &mut players {
+        for &(x, y) in &rules.islands {
+            player.add_island(x, y);
+        }
+        if rules.fleet.iter().any(|shape| !shape.is_straight()) {
+            player.allow_bent_ships();
+        }
+    }
+    players
+}
+
+impl Game {
+    pub fn new(rules: Rules) -> Game {
+        Game {
+            players: new_players(&rules),
+            ships_to_place: [rules.fleet.clone(), rules.fleet.clone()],
+            confirmed: [false, false],
+            phase: Phase::Placement,
+            arsenals: [rules.arsenal, rules.arsenal],
+            rules,
+            history: Vec::new(),
+            pending: Vec::new(),
+            done: Vec::new(),
+            undone: Vec::new(),
+            undo_allowed: true,
+            hidden: None,
+            observers: Vec::new(),
+        }
+    }
+
+    pub fn subscribe(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// Every shot and special attack fired so far and who fired it, in
+    /// order. Shots of an unfinished volley only show up once it is
+    /// complete.
+    pub fn history(&self) -> &[(Side, Attack)] {
+        &self.history
+    }
+
+    /// Special attacks `side` has left.
+    pub fn arsenal(&self, side: Side) -> Arsenal {
+        self.arsenals[side.index()]
+    }
+
+    /// Whether the side on turn fired the last volley as well, and got
+    /// another turn for hitting with it.
+    pub fn shoots_again(&self) -> bool {
+        match (self.phase, self.history.last()) {
+            (Phase::Shooting(side), Some(&(last, _))) => side == last && self.pending.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Shots of the volley the side on turn is firing, that haven't
+    /// landed yet.
+    pub fn pending_shots(&self) -> &[Shot] {
+        &self.pending
+    }
+
+    /// How many shots `side` fires in a turn. Never more than the cells
+    /// they haven't shot at yet.
+    pub fn volley_size(&self, side: Side) -> usize {
+        let shots = match self.rules.salvo {
+            Salvo::Off => 1,
+            Salvo::ShipsAfloat => self.ships_afloat(side),
+            Salvo::Fixed(shots) => shots,
+        };
+
+        let checked = self.player(side).checked_field();
+        let FieldSize { width, height } = self.rules.size;
+        let left = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| matches!(checked.at(x, y), CheckedCell::Unchecked | CheckedCell::KnownEmpty))
+            .count();
+        shots.min(left).max(1)
+    }
+
+    /// Whether `side` may fire at `shot` now.
+    pub fn can_shoot(&self, side: Side, shot: Shot) -> Result<(), GameError> {
+        self.check_turn(side)?;
+        if shot.x >= self.rules.size.width || shot.y >= self.rules.size.height {
+            return Err(GameError::OutOfBounds);
+        }
+        if self.pending.iter().any(|aimed| aimed.x == shot.x && aimed.y == shot.y) {
+            return Err(GameError::AlreadyAimed);
+        }
+
+        match self.player(side.other()).player_field().at(shot.x, shot.y) {
+            FieldCell::EnemyMiss | FieldCell::Hit | FieldCell::Dead | FieldCell::Crater => {
+                Err(GameError::InvalidShot("Already shot in this place!".to_string()))
+            }
+            FieldCell::Island => Err(GameError::InvalidShot("There's an island in this place!".to_string())),
+            FieldCell::Empty | FieldCell::Ship | FieldCell::Mine => Ok(()),
+        }
+    }
+
+    pub fn player(&self, side: Side) -> &Player {
+        &self.players[side.index()]
+    }
+
+    /// How many ships of `side` aren't sunk yet.
+    pub fn ships_afloat(&self, side: Side) -> usize {
+        match self.hidden {
+            // Only the ships that went down are known.
+            Some(hidden) if hidden == side => self.rules.fleet.len().saturating_sub(self.player(side).ships().len()),
+            _ => self.player(side).ships_afloat(),
+        }
+    }
+
+    /// Plays without knowing the fleet of `side`, like over the network.
+    /// Their fleet can be confirmed without placing it, attacks at it
+    /// need the `Answer` they gave, and no move can be taken back.
+    pub fn hide_fleet(&mut self, side: Side) {
+        self.hidden = Some(side);
+        self.disable_undo();
+    }
+
+    /// What the attacks `side` fires this turn do to the fleet of the
+    /// other side, to tell them when they can't see it. The attacks must
+    /// be a whole volley, or one special attack.
+    pub fn answer(&self, side: Side, attacks: &[Attack]) -> Result<Answer, GameError> {
+        let FieldSize { width, height } = self.rules.size;
+        let target = self.player(side.other());
+
+        let cells: Vec<Shot> = match *attacks {
+            [Attack::Special(special)] => {
+                self.can_use_special(side, special)?;
+                match special {
+                    Special::Bomb(centre) => weapons::area(centre, width, height)
+                        .filter(|&(x, y)| matches!(target.player_field().at(x, y), FieldCell::Empty | FieldCell::Ship | FieldCell::Mine))
+                        .map(|(x, y)| Shot { x, y })
+                        .collect(),
+                    Special::Torpedo { row, heading } => self.torpedo_targets(side, row, heading),
+                    Special::Sonar(centre) => {
+                        return Ok(Answer { ship_cells: target.ship_cells_around(centre), ..Answer::default() });
+                    }
+                }
+            }
+            _ => {
+                let mut shots = Vec::new();
+                for attack in attacks {
+                    let Attack::Shot(shot) = *attack else {
+                        return Err(GameError::VolleyUnfinished);
+                    };
+                    self.can_shoot(side, shot)?;
+                    if shots.iter().any(|aimed: &Shot| aimed.x == shot.x && aimed.y == shot.y) {
+                        return Err(GameError::AlreadyAimed);
+                    }
+                    shots.push(shot);
+                }
+                if shots.len() != self.volley_size(side) {
+                    return Err(GameError::VolleyUnfinished);
+                }
+                shots
+            }
+        };
+
+        let mut answer = Answer::default();
+        for (index, &shot) in cells.iter().enumerate() {
+            let outcome = match target.player_field().at(shot.x, shot.y) {
+                FieldCell::Ship => {
+                    let ship = *target.ship_at(shot.x, shot.y).expect("every ship cell belongs to a placed ship");
+                    // Sunk once every other cell of it is hit, before or
+                    // by this attack.
+                    let sunk = ship.cells().all(|(x, y)| {
+                        (x, y) == (shot.x, shot.y)
+                            || target.player_field().at(x, y) == FieldCell::Hit
+                            || cells[..index].iter().any(|earlier| (earlier.x, earlier.y) == (x, y))
+                    });
+                    if sunk { Outcome::Sunk(ship) } else { Outcome::Hit }
+                }
+                FieldCell::Mine => Outcome::Mine,
+                _ => Outcome::Miss,
+            };
+            answer.cells.push((shot, outcome));
+        }
+        Ok(answer)
+    }
+
+    /// The ships `side` still has to place.
+    pub fn ships_to_place(&self, side: Side) -> &[Shape] {
+        &self.ships_to_place[side.index()]
+    }
+
+    /// How many mines `side` still has to hide.
+    pub fn mines_to_place(&self, side: Side) -> usize {
+        self.rules.mines.saturating_sub(self.player(side).mines().len())
+    }
+
+    /// Whether `side` has locked their fleet in.
+    pub fn is_fleet_confirmed(&self, side: Side) -> bool {
+        self.confirmed[side.index()]
+    }
+
+    /// Places every ship of `fleet` for `side` and locks the fleet in.
+    pub fn place_fleet(&mut self, side: Side, fleet: &[Ship]) -> Result<(), GameError> {
+        self.place_ships(side, fleet)?;
+        self.handle(Command::ConfirmFleet(side))?;
+        Ok(())
+    }
+
+    /// Hides every one of `mines` for `side`, and places `fleet` and locks
+    /// it in like `place_fleet`.
+    pub fn place_fleet_and_mines(&mut self, side: Side, fleet: &[Ship], mines: &[Shot]) -> Result<(), GameError> {
+        for mine in mines {
+            self.handle(Command::PlaceMine(side, *mine))?;
+        }
+        self.place_fleet(side, fleet)
+    }
+
+    fn place_ships(&mut self, side: Side, fleet: &[Ship]) -> Result<(), GameError> {
+        for ship in fleet {
+            self.handle(Command::PlaceShip(side, *ship))?;
+        }
+        Ok(())
+    }
+
+    /// Whether `side` may use `special` now. It takes the whole turn, so
+    /// not in the middle of a volley, and it must have something to hit.
+    pub fn can_use_special(&self, side: Side, special: Special) -> Result<(), GameError> {
+        self.check_turn(side)?;
+        if !self.pending.is_empty() {
+            return Err(GameError::VolleyUnfinished);
+        }
+        if self.arsenal(side).count(special.weapon()) == 0 {
+            return Err(GameError::NoWeaponLeft(special.weapon()));
+        }
+
+        let FieldSize { width, height } = self.rules.size;
+        let target = self.player(side.other()).player_field();
+        let unshot = |&(x, y): &(usize, usize)| matches!(target.at(x, y), FieldCell::Empty | FieldCell::Ship | FieldCell::Mine);
+        match special {
+            Special::Bomb(centre) | Special::Sonar(centre) if centre.x >= width || centre.y >= height => {
+                Err(GameError::OutOfBounds)
+            }
+            Special::Torpedo { row, .. } if row >= height => Err(GameError::OutOfBounds),
+            Special::Bomb(centre) if !weapons::area(centre, width, height).any(|cell| unshot(&cell)) => {
+                Err(GameError::NothingToAttack)
+            }
+            Special::Torpedo { row, heading } if self.torpedo_targets(side, row, heading).is_empty() => {
+                Err(GameError::NothingToAttack)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn can_place(&self, side: Side, ship: &Ship) -> Result<(), GameError> {
+        self.check_editable(side)?;
+        if !self.ships_to_place(side).contains(&ship.shape) {
+            return Err(GameError::Placement(ShipPlacementError::NoShipsLeft(ship.shape)));
+        }
+
+        self.player(side).can_place(ship).map_err(GameError::Placement)
+    }
+
+    pub fn can_place_mine(&self, side: Side, mine: Shot) -> Result<(), GameError> {
+        self.check_editable(side)?;
+        if self.mines_to_place(side) == 0 {
+            return Err(GameError::NoMinesLeft);
+        }
+
+        self.player(side).can_place_mine(mine).map_err(GameError::Placement)
+    }
+
+    pub fn handle(&mut self, command: Command) -> Result<Vec<Event>, GameError> {
+        self.handle_answered(command, &Answer::default())
+    }
+
+    /// Like `handle`, for an attack at a hidden fleet, which lands as its
+    /// owner's `answer` says.
+    pub fn handle_answered(&mut self, command: Command, answer: &Answer) -> Result<Vec<Event>, GameError> {
+        let events = self.apply(command, answer)?;
+        self.undone.clear();
+
+        self.notify(&events);
+        Ok(events)
+    }
+
+    /// Makes `undo` and `redo` fail from now on, for games that count.
+    pub fn disable_undo(&mut self) {
+        self.undo_allowed = false;
+        self.undone.clear();
+    }
+
+    pub fn undo_allowed(&self) -> bool {
+        self.undo_allowed
+    }
+
+    /// The command `undo` would take back.
+    pub fn last_command(&self) -> Option<Command> {
+        self.done.last().copied()
+    }
+
+    /// The command `redo` would do again.
+    pub fn next_redo(&self) -> Option<Command> {
+        self.undone.last().copied()
+    }
+
+    /// Takes the last command back. The fields are rebuilt from the
+    /// commands before it, which also un-sinks ships and forgets whatever
+    /// was inferred from the shot.
+    pub fn undo(&mut self) -> Result<Command, GameError> {
+        if !self.undo_allowed {
+            return Err(GameError::UndoDisabled);
+        }
+        let command = self.done.pop().ok_or(GameError::NothingToUndo)?;
+
+        let done = std::mem::take(&mut self.done);
+        self.players = new_players(&self.rules);
+        self.ships_to_place = [self.rules.fleet.clone(), self.rules.fleet.clone()];
+        self.confirmed = [false, false];
+        self.phase = Phase::Placement;
+        self.history.clear();
+        self.arsenals = [self.rules.arsenal, self.rules.arsenal];
+        self.pending.clear();
+        for command in done {
+            self.apply(command, &Answer::default()).expect("the command worked the first time");
+        }
+
+        self.undone.push(command);
+        self.notify(&[Event::Undone(command)]);
+        Ok(command)
+    }
+
+    /// Does the last command taken back by `undo` again.
+    pub fn redo(&mut self) -> Result<Vec<Event>, GameError> {
+        let command = self.undone.pop().ok_or(GameError::NothingToRedo)?;
+        let events = self.apply(command, &Answer::default()).expect("the command worked before it was taken back");
+
+        self.notify(&events);
+        Ok(events)
+    }
+
+    fn apply(&mut self, command: Command, answer: &Answer) -> Result<Vec<Event>, GameError> {
+        let events = match command {
+            Command::PlaceShip(side, ship) => self.place_ship(side, ship),
+            Command::RemoveShip(side, ship) => self.remove_ship(side, ship),
+            Command::MoveShip { side, from, to } => self.move_ship(side, from, to),
+            Command::ClearFleet(side) => self.clear_fleet(side),
+            Command::PlaceMine(side, mine) => self.place_mine(side, mine),
+            Command::RemoveMine(side, mine) => self.remove_mine(side, mine),
+            Command::ConfirmFleet(side) => self.confirm_fleet(side),
+            Command::Shoot(side, shot) => self.shoot(side, shot, answer),
+            Command::UseSpecial(side, special) => self.use_special(side, special, answer),
+        }?;
+        self.done.push(command);
+        Ok(events)
+    }
+
+    fn notify(&mut self, events: &[Event]) {
+        for observer in &mut self.observers {
+            for event in events {
+                observer.notify(event);
+            }
+        }
+    }
+
+    fn place_ship(&mut self, side: Side, ship: Ship) -> Result<Vec<Event>, GameError> {
+        self.can_place(side, &ship)?;
+
+        self.players[side.index()].place_ship(&ship);
+        let left = &mut self.ships_to_place[side.index()];
+        let position = left.iter().position(|shape| *shape == ship.shape).expect("checked by can_place");
+        left.swap_remove(position);
+
+        Ok(vec![Event::ShipPlaced(side, ship)])
+    }
+
+    fn remove_ship(&mut self, side: Side, ship: Ship) -> Result<Vec<Event>, GameError> {
+        self.check_editable(side)?;
+        if !self.players[side.index()].remove_ship(&ship) {
+            return Err(GameError::NoSuchShip);
+        }
+        self.ships_to_place[side.index()].push(ship.shape);
+
+        Ok(vec![Event::ShipRemoved(side, ship)])
+    }
+
+    fn move_ship(&mut self, side: Side, from: Ship, to: Ship) -> Result<Vec<Event>, GameError> {
+        self.check_editable(side)?;
+        if from.shape != to.shape {
+            return Err(GameError::NoSuchShip);
+        }
+
+        let player = &mut self.players[side.index()];
+        let index = player.ships().iter().position(|placed| *placed == from).ok_or(GameError::NoSuchShip)?;
+        player.replace_ship(index, &to).map_err(GameError::Placement)?;
+
+        Ok(vec![Event::ShipMoved { side, from, to }])
+    }
+
+    fn clear_fleet(&mut self, side: Side) -> Result<Vec<Event>, GameError> {
+        self.check_editable(side)?;
+        let player = &mut self.players[side.index()];
+        for ship in player.ships().to_vec() {
+            player.remove_ship(&ship);
+            self.ships_to_place[side.index()].push(ship.shape);
+        }
+
+        Ok(vec![Event::FleetCleared(side)])
+    }
+
+    fn place_mine(&mut self, side: Side, mine: Shot) -> Result<Vec<Event>, GameError> {
+        self.can_place_mine(side, mine)?;
+        self.players[side.index()].place_mine(mine);
+
+        Ok(vec![Event::MinePlaced(side, mine)])
+    }
+
+    fn remove_mine(&mut self, side: Side, mine: Shot) -> Result<Vec<Event>, GameError> {
+        self.check_editable(side)?;
+        if !self.players[side.index()].remove_mine(mine) {
+            return Err(GameError::NoSuchMine);
+        }
+
+        Ok(vec![Event::MineRemoved(side, mine)])
+    }
+
+    fn confirm_fleet(&mut self, side: Side) -> Result<Vec<Event>, GameError> {
+        self.check_editable(side)?;
+        let complete = self.ships_to_place(side).is_empty() && self.mines_to_place(side) == 0;
+        if !complete && self.hidden != Some(side) {
+            return Err(GameError::FleetIncomplete);
+        }
+        self.confirmed[side.index()] = true;
+
+        let mut events = vec![Event::FleetPlaced(side)];
+        if self.confirmed == [true, true] {
+            self.phase = Phase::Shooting(Side::First);
+            events.push(Event::TurnChanged(Side::First));
+        }
+        Ok(events)
+    }
+
+    /// Whether it's the turn of `side` to shoot.
+    fn check_turn(&self, side: Side) -> Result<(), GameError> {
+        match self.phase {
+            Phase::Shooting(turn) if turn == side => Ok(()),
+            Phase::Shooting(_) => Err(GameError::NotYourTurn),
+            Phase::Placement => Err(GameError::NotShooting),
+            Phase::Finished(_) => Err(GameError::GameOver),
+        }
+    }
+
+    /// Whether `side` may still change their fleet.
+    fn check_editable(&self, side: Side) -> Result<(), GameError> {
+        if self.phase != Phase::Placement {
+            return Err(GameError::NotPlacing);
+        }
+        if self.confirmed[side.index()] {
+            return Err(GameError::FleetLocked);
+        }
+        Ok(())
+    }
+
+    /// Aims `shot`, and fires the whole volley once it is complete. Every
+    /// shot of it lands at the same time, so nothing is known about any of
+    /// them before.
+    fn shoot(&mut self, side: Side, shot: Shot, answer: &Answer) -> Result<Vec<Event>, GameError> {
+        self.can_shoot(side, shot)?;
+
+        let volley_size = self.volley_size(side);
+        self.pending.push(shot);
+        let mut events = vec![Event::ShotFired { side, shot }];
+        if self.pending.len() < volley_size {
+            return Ok(events);
+        }
+
+        let volley = std::mem::take(&mut self.pending);
+        let hit = self.fire_at(side, &volley, answer, &mut events)?;
+        self.history.extend(volley.iter().map(|&shot| (side, Attack::Shot(shot))));
+
+        self.end_turn(side, hit, &mut events);
+        Ok(events)
+    }
+
+    /// Fires `special` for `side`. Bombs and torpedoes land like shots do
+    /// and can earn another turn, a sonar ping only tells what it found.
+    fn use_special(&mut self, side: Side, special: Special, answer: &Answer) -> Result<Vec<Event>, GameError> {
+        self.can_use_special(side, special)?;
+        let hidden = self.hidden == Some(side.other());
+        self.arsenals[side.index()].take(special.weapon());
+
+        let FieldSize { width, height } = self.rules.size;
+        let target = self.player(side.other()).player_field();
+        let mut events = vec![Event::SpecialUsed { side, special }];
+
+        let hit = match special {
+            Special::Bomb(centre) => {
+                let cells: Vec<Shot> = weapons::area(centre, width, height)
+                    .filter(|&(x, y)| matches!(target.at(x, y), FieldCell::Empty | FieldCell::Ship | FieldCell::Mine))
+                    .map(|(x, y)| Shot { x, y })
+                    .collect();
+                self.fire_at(side, &cells, answer, &mut events)?
+            }
+            Special::Torpedo { row, heading } => {
+                let mut cells = self.torpedo_targets(side, row, heading);
+                if hidden {
+                    // Ships look like open water here, so only the owner
+                    // knows where it stopped: at the first cell it hit.
+                    let run = answer.cells.iter()
+                        .position(|(_, outcome)| !matches!(outcome, Outcome::Miss))
+                        .map_or(cells.len(), |stop| stop + 1);
+                    cells.truncate(run);
+                }
+                self.fire_at(side, &cells, answer, &mut events)?
+            }
+            Special::Sonar(centre) if hidden => {
+                if answer.ship_cells > weapons::area(centre, width, height).count() {
+                    return Err(GameError::BadAnswer(format!("{} ship cells around {centre}", answer.ship_cells)));
+                }
+                let reading = SonarReading { centre, ship_cells: answer.ship_cells };
+                self.players[side.index()].record_ping(reading);
+                events.push(Event::SonarPing { side, reading });
+                false
+            }
+            Special::Sonar(centre) => {
+                let (shooter, target) = self.pair_mut(side);
+                let reading = shooter.ping(target, centre);
+                events.push(Event::SonarPing { side, reading });
+                false
+            }
+        };
+        self.history.push((side, Attack::Special(special)));
+
+        self.end_turn(side, hit, &mut events);
+        Ok(events)
+    }
+
+    /// The cells a torpedo of `side` fires at on its way along `row`. It
+    /// runs over open water and stops at the first ship, whether that's
+    /// still intact or not, at a mine or on land.
+    fn torpedo_targets(&self, side: Side, row: usize, heading: Heading) -> Vec<Shot> {
+        let target = self.player(side.other()).player_field();
+        let mut cells = Vec::new();
+        for (x, y) in weapons::torpedo_path(row, heading, self.rules.size.width) {
+            match target.at(x, y) {
+                FieldCell::Empty => cells.push(Shot { x, y }),
+                FieldCell::EnemyMiss | FieldCell::Crater => {}
+                FieldCell::Ship | FieldCell::Mine => {
+                    cells.push(Shot { x, y });
+                    break;
+                }
+                FieldCell::Hit | FieldCell::Dead | FieldCell::Island => break,
+            }
+        }
+        cells
+    }
+
+    /// Fires at every one of `shots` for `side`, adding what each did to
+    /// `events`. Returns whether any of them hit. A mine that goes off
+    /// doesn't count as a hit.
+    fn fire_at(&mut self, side: Side, shots: &[Shot], answer: &Answer, events: &mut Vec<Event>) -> Result<bool, GameError> {
+        if self.hidden == Some(side.other()) {
+            return self.fire_at_hidden(side, shots, answer, events);
+        }
+        let hidden_shooter = self.hidden == Some(side);
+        let fleet = self.rules.fleet.clone();
+        let (shooter, target) = self.pair_mut(side);
+        let mut hit = false;
+        let mut mines = 0;
+
+        for &shot in shots {
+            shooter.shoot(target, shot.x, shot.y).map_err(GameError::InvalidShot)?;
+
+            let outcome = shooter.checked_field().at(shot.x, shot.y);
+            hit |= matches!(outcome, CheckedCell::Hit | CheckedCell::Kill);
+            events.push(match outcome {
+                CheckedCell::Hit => Event::Hit { side, shot },
+                CheckedCell::Kill => {
+                    let ship = target.ship_at(shot.x, shot.y).expect("a sunk ship was placed");
+                    Event::ShipSunk { side, shot, cells: ship.cells().collect() }
+                }
+                CheckedCell::Mine if hidden_shooter => {
+                    // Only the owner of the hidden fleet knows which of
+                    // their ships it damaged.
+                    let told = answer.damage.get(mines).copied()
+                        .ok_or_else(|| GameError::BadAnswer(format!("nothing about the mine at {shot}")))?;
+                    mines += 1;
+                    if let Some((cell, outcome)) = told {
+                        if !matches!(outcome, Outcome::Hit | Outcome::Sunk(_)) {
+                            return Err(GameError::BadAnswer(format!("the mine at {shot} didn't damage {cell}")));
+                        }
+                        check_sunk(outcome, &fleet, shooter.ships())?;
+                        target.record_shot(shooter, cell.x, cell.y, outcome).map_err(GameError::BadAnswer)?;
+                    }
+                    let sunk = matches!(told, Some((_, Outcome::Sunk(_))));
+                    Event::MineHit { side, shot, damaged: told.map(|(cell, _)| cell), sunk }
+                }
+                CheckedCell::Mine => set_off_mine(target, shooter, side, shot)?,
+                _ => Event::Miss { side, shot },
+            });
+        }
+        if hidden_shooter && mines != answer.damage.len() {
+            return Err(GameError::BadAnswer(format!("damage from {} mines, not {mines}", answer.damage.len())));
+        }
+        Ok(hit)
+    }
+
+    /// `fire_at` a hidden fleet, where every one of `shots` lands as the
+    /// `answer` says.
+    fn fire_at_hidden(&mut self, side: Side, shots: &[Shot], answer: &Answer, events: &mut Vec<Event>) -> Result<bool, GameError> {
+        if answer.cells.len() != shots.len() {
+            return Err(GameError::BadAnswer(format!("{} cells for {} shots", answer.cells.len(), shots.len())));
+        }
+        let fleet = self.rules.fleet.clone();
+        let (shooter, target) = self.pair_mut(side);
+        let mut hit = false;
+
+        for (&shot, &(cell, outcome)) in shots.iter().zip(&answer.cells) {
+            if (cell.x, cell.y) != (shot.x, shot.y) {
+                return Err(GameError::BadAnswer(format!("{cell} instead of {shot}")));
+            }
+            check_sunk(outcome, &fleet, target.ships())?;
+            shooter.record_shot(target, shot.x, shot.y, outcome).map_err(GameError::BadAnswer)?;
+
+            hit |= matches!(outcome, Outcome::Hit | Outcome::Sunk(_));
+            events.push(match outcome {
+                Outcome::Miss => Event::Miss { side, shot },
+                Outcome::Hit => Event::Hit { side, shot },
+                Outcome::Sunk(ship) => Event::ShipSunk { side, shot, cells: ship.cells().collect() },
+                Outcome::Mine => set_off_mine(target, shooter, side, shot)?,
+            });
+        }
+        Ok(hit)
+    }
+
+    /// Whether `side` has no ship left. Of a hidden fleet, only the ships
+    /// that went down are known, so it's lost once all of them did.
+    fn has_lost(&self, side: Side) -> bool {
+        match self.hidden {
+            Some(hidden) if hidden == side => self.ships_afloat(side) == 0,
+            _ => self.player(side).did_lose(),
+        }
+    }
+
+    /// Ends the turn of `side`: the game is won, or lost to a mine, or
+    /// the turn goes to whoever shoots next.
+    fn end_turn(&mut self, side: Side, hit: bool, events: &mut Vec<Event>) {
+        if self.has_lost(side.other()) {
+            self.phase = Phase::Finished(side);
+            events.push(Event::GameOver { winner: side });
+        } else if self.has_lost(side) {
+            self.phase = Phase::Finished(side.other());
+            events.push(Event::GameOver { winner: side.other() });
+        } else {
+            let next = match self.rules.turns {
+                TurnOrder::HitShootsAgain if hit => side,
+                _ => side.other(),
+            };
+            self.phase = Phase::Shooting(next);
+            events.push(Event::TurnChanged(next));
+        }
+    }
+
+    /// The player on `side` and their opponent.
+    fn pair_mut(&mut self, side: Side) -> (&mut Player, &mut Player) {
+        let [first, second] = &mut self.players;
+        match side {
+            Side::First => (first, second),
+            Side::Second => (second, first),
+        }
+    }
+}
+
+/// Sets off the mine of `owner` that `side` shot at, which damages the
+/// ship cell of `victim` closest to it. The owner learns what it did as
+/// if they had fired at the damaged cell themselves.
+fn set_off_mine(owner: &mut Player, victim: &mut Player, side: Side, shot: Shot) -> Result<Event, GameError> {
+    let damaged = victim.nearest_ship_cell(shot.x, shot.y);
+    let mut sunk = false;
+    if let Some((x, y)) = damaged {
+        owner.shoot(victim, x, y).map_err(GameError::InvalidShot)?;
+        sunk = owner.checked_field().at(x, y) == CheckedCell::Kill;
+    }
+    Ok(Event::MineHit { side, shot, damaged: damaged.map(|(x, y)| Shot { x, y }), sunk })
+}
+
+/// Whether a hidden fleet of `fleet`, with `sunk` down already, still has
+/// the ship that `outcome` says went down.
+fn check_sunk(outcome: Outcome, fleet: &[Shape], sunk: &[Ship]) -> Result<(), GameError> {
+    let Outcome::Sunk(ship) = outcome else {
+        return Ok(());
+    };
+    let down = sunk.iter().filter(|other| other.shape == ship.shape).count();
+    if down >= fleet.iter().filter(|shape| **shape == ship.shape).count() {
+        return Err(GameError::BadAnswer(format!("one {} too many", ship.shape.describe())));
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum GameError {
+    NotPlacing,
+    NotShooting,
+    NotYourTurn,
+    GameOver,
+    OutOfBounds,
+    Placement(ShipPlacementError),
+    InvalidShot(String),
+    /// The volley being fired already has a shot at that cell.
+    AlreadyAimed,
+    /// Special attacks can't be mixed into a volley.
+    VolleyUnfinished,
+    NoWeaponLeft(Weapon),
+    /// Everything the special attack could reach was shot at already.
+    NothingToAttack,
+    NoSuchShip,
+    /// Mirroring the ship wouldn't change the cells it covers.
+    Symmetric,
+    NoMinesLeft,
+    NoSuchMine,
+    FleetIncomplete,
+    FleetLocked,
+    UndoDisabled,
+    NothingToUndo,
+    NothingToRedo,
+    /// The owner of a hidden fleet told something about it that can't be
+    /// true.
+    BadAnswer(String),
+}
+
+impl Display for GameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameError::NotPlacing => write!(f, "ships can only be placed before the shooting starts"),
+            GameError::NotShooting => write!(f, "can't shoot before both fleets are placed"),
+            GameError::NotYourTurn => write!(f, "it's not your turn"),
+            GameError::GameOver => write!(f, "the game is already over"),
+            GameError::OutOfBounds => write!(f, "the shot is outside of the field"),
+            GameError::Placement(inner) => write!(f, "{inner}"),
+            GameError::InvalidShot(inner) => write!(f, "{inner}"),
+            GameError::AlreadyAimed => write!(f, "this volley already has a shot there"),
+            GameError::VolleyUnfinished => write!(f, "finish the volley first"),
+            GameError::NoWeaponLeft(weapon) => write!(f, "there's no {weapon} left"),
+            GameError::NothingToAttack => write!(f, "everything there was shot at already"),
+            GameError::NoSuchShip => write!(f, "there's no such ship"),
+            GameError::Symmetric => write!(f, "the ship looks the same mirrored"),
+            GameError::NoMinesLeft => write!(f, "all mines are placed already"),
+            GameError::NoSuchMine => write!(f, "there's no mine there"),
+            GameError::FleetIncomplete => write!(f, "not all ships and mines are placed yet"),
+            GameError::FleetLocked => write!(f, "the fleet is already confirmed"),
+            GameError::UndoDisabled => write!(f, "taking moves back is turned off for this game"),
+            GameError::NothingToUndo => write!(f, "there's nothing to take back"),
+            GameError::NothingToRedo => write!(f, "there's nothing to redo"),
+            GameError::BadAnswer(reason) => write!(f, "{reason}"),
+        }
+    }
+}
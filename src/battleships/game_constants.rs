@@ -1,6 +1,21 @@
 pub const FIELD_SIZE: usize = 10;
+/// The largest field a game can be played on.
+pub const MAX_FIELD_SIZE: usize = 100;
+/// The most shots a fixed salvo can have.
+pub const MAX_SALVO: usize = 255;
+/// The most special attacks of one kind a player can start with.
+pub const MAX_WEAPONS: usize = 255;
+/// The most ships a fleet can have.
+pub const MAX_FLEET: usize = 255;
 
-pub const SHIP_COUNT: u8 = 1;
+use super::shape::Shape;
+
+/// Every ship in a standard fleet.
+pub const FLEET: [Shape; 10] = [
+    Shape::Line(4), Shape::Line(3), Shape::Line(3), Shape::Line(2), Shape::Line(2),
+    Shape::Line(2), Shape::Line(1), Shape::Line(1), Shape::Line(1), Shape::Line(1),
+];
+
+/// One straight four and one of each tetromino.
+pub const TETRIS_FLEET: [Shape; 5] = [Shape::Line(4), Shape::Square, Shape::T, Shape::L, Shape::Z];
 
-pub const FIRST_LETTER: char = 'A';
-pub const LAST_LETTER: char = (('A' as u8) + FIELD_SIZE as u8 - 1) as char;
@@ -1,5 +1,18 @@
 pub mod field;
+pub mod bitboard;
 pub mod player;
 pub mod ship;
+pub mod shape;
 pub mod shot;
+pub mod coordinate;
 pub mod game_constants;
+pub mod rules;
+pub mod weapons;
+pub mod terrain;
+pub mod feasibility;
+pub mod game;
+pub mod events;
+pub mod save;
+pub mod replay;
+pub mod notation;
+pub mod ai;
@@ -0,0 +1,592 @@
+//! A PGN-like notation for sharing finished (or unfinished) games. A tag
+//! block describes the game, followed by the shots in numbered pairs:
+//!
+//! ```text
+//! [Player1 "Alice"]
+//! [Player2 "Computer"]
+//! [Date "2026.10.19"]
+//! [Size "10"]
+//! [Fleet "4 3 3 2 2 2 1 1 1 1"]
+//! [Touching "no-touch"]
+//! [Ships1 "4:A:1:h 3:C:1:h ..."]
+//! [Ships2 "4:G:2:v 3:E:5:h ..."]
+//! [Result "1-0"]
+//!
+//! 1. E:5x D:3+ 2. B:7# D:4x 3. ... 1-0
+//! ```
+//!
+//! Every pair starts with Player 1's shot. When Player 2 shoots without
+//! Player 1 shooting first in that pair, the number is written as `3...`.
+//! Each shot is marked with what it did: `x` missed, `+` hit, `#` sunk a
+//! ship. The result is `1-0` or `0-1` for a finished game and `*` for one
+//! still being played.
+//!
+//! Games played with salvos have a `[Salvo "3"]` tag (or `"ships"`), and
+//! the shots of a volley are joined by commas, like `E:5x,D:3+,J:9x`.
+//! Their marks say what every shot did once the whole volley landed.
+//! When a hit earns another turn, there's a `[Turns "hit-again"]` tag and
+//! a player can have several moves in a row.
+//!
+//! Games with special attacks have an `[Arsenal "1,2,1"]` tag. A bomb is
+//! written `*B:3+` and a torpedo `>C#` or `<Cx`, by the way it runs along
+//! the row, marked with the best thing they did. A sonar ping is written
+//! `?E:5=2`, with the number of ship cells it found.
+//!
+//! Games with terrain have an `[Islands "E:5 E:6"]` tag with the cells of
+//! land, and a `[Mines "2"]` tag with how many mines every player hides,
+//! along with `[Mines1 "..."]` and `[Mines2 "..."]` for where they are. A
+//! shot that set off a mine is marked `!`.
+
+use std::{fmt::Display, time::{SystemTime, UNIX_EPOCH}};
+
+use super::{
+    game::{Game, Side, Phase, Command},
+    events::Event,
+    field::CheckedCell,
+    game_constants::MAX_FIELD_SIZE,
+    rules::{Rules, FieldSize, Salvo, TurnOrder},
+    shape,
+    ship::Ship,
+    shot::Shot,
+    terrain,
+    weapons::{Arsenal, Attack, Special, Heading},
+};
+use crate::utilities::conversions;
+
+/// A game read from its notation.
+pub struct NotatedGame {
+    pub game: Game,
+    pub players: [String; 2],
+    pub date: String,
+}
+
+/// Writes `game` down, with today's date.
+pub fn export(game: &Game, players: [&str; 2]) -> String {
+    let rules = game.rules();
+    let ships = |side: Side| -> String {
+        let ships: Vec<String> = game.player(side).ships().iter().map(|ship| ship.to_string()).collect();
+        ships.join(" ")
+    };
+
+    let mut tags = vec![
+        ("Player1", players[0].to_string()),
+        ("Player2", players[1].to_string()),
+        ("Date", today()),
+        ("Size", rules.size.to_string()),
+        ("Fleet", shape::format_fleet(&rules.fleet)),
+        ("Touching", rules.touching.to_string()),
+    ];
+    if rules.salvo != Salvo::Off {
+        tags.push(("Salvo", rules.salvo.to_string()));
+    }
+    if rules.turns != TurnOrder::Alternate {
+        tags.push(("Turns", rules.turns.to_string()));
+    }
+    if !rules.arsenal.is_empty() {
+        tags.push(("Arsenal", rules.arsenal.to_string()));
+    }
+    if !rules.islands.is_empty() {
+        tags.push(("Islands", terrain::format_cells(rules.islands.iter().copied())));
+    }
+    tags.extend([
+        ("Ships1", ships(Side::First)),
+        ("Ships2", ships(Side::Second)),
+    ]);
+    if rules.mines > 0 {
+        let mines = |side: Side| terrain::format_cells(game.player(side).mines().iter().map(|mine| (mine.x, mine.y)));
+        tags.extend([
+            ("Mines", rules.mines.to_string()),
+            ("Mines1", mines(Side::First)),
+            ("Mines2", mines(Side::Second)),
+        ]);
+    }
+    tags.extend([
+        ("Result", result(game.phase()).to_string()),
+    ]);
+
+    let mut text = String::new();
+    for (name, value) in tags {
+        text.push_str(&format!("[{name} \"{}\"]\n", value.replace('"', "'")));
+    }
+    text.push('\n');
+
+    // Replayed on a fresh game, since the history only has the shots and
+    // not what they did.
+    let placed = || {
+        let mut replayed = Game::new(rules.clone());
+        for side in [Side::First, Side::Second] {
+            if game.is_fleet_confirmed(side) {
+                let player = game.player(side);
+                replayed.place_fleet_and_mines(side, player.ships(), player.mines())
+                    .expect("the fleet was placed in the original game");
+            }
+        }
+        replayed
+    };
+    let mut replayed = placed();
+
+    // The shots grouped into volleys, the same way the game did.
+    let mut volleys: Vec<(Side, Vec<Attack>)> = Vec::new();
+    let mut grouped = placed();
+    for &(side, attack) in game.history() {
+        match volleys.last_mut() {
+            Some((_, attacks)) if !grouped.pending_shots().is_empty() => attacks.push(attack),
+            _ => volleys.push((side, vec![attack])),
+        }
+        grouped.handle(Command::attack(side, attack)).expect("the shot was fired in the original game");
+    }
+
+    let mut tokens = Vec::new();
+    let mut number = 1;
+    let mut pair_open = false;
+    for (side, attacks) in volleys {
+        match side {
+            Side::First => {
+                if pair_open {
+                    number += 1;
+                }
+                tokens.push(format!("{number}."));
+                pair_open = true;
+            }
+            Side::Second => {
+                if !pair_open {
+                    tokens.push(format!("{number}..."));
+                }
+                pair_open = false;
+            }
+        }
+
+        let mut events = Vec::new();
+        for &attack in &attacks {
+            events = replayed.handle(Command::attack(side, attack)).expect("the shot was fired in the original game");
+        }
+        let marked: Vec<String> = attacks.iter()
+            .map(|attack| match *attack {
+                Attack::Shot(shot) => {
+                    format!("{shot}{}", annotation(replayed.player(side).checked_field().at(shot.x, shot.y)))
+                }
+                Attack::Special(special) => special_token(special, &events),
+            })
+            .collect();
+        tokens.push(marked.join(","));
+
+        if side == Side::Second {
+            number += 1;
+        }
+    }
+    tokens.push(result(game.phase()).to_string());
+
+    // Wrapped like PGN, so the text stays readable anywhere.
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > 80 {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    text.push_str(&line);
+    text.push('\n');
+
+    text
+}
+
+/// Reads a game back, firing every shot through the game again. Every
+/// shot must be legal and do what its annotation says.
+pub fn import(text: &str) -> Result<NotatedGame, NotationError> {
+    let mut tags: Vec<(String, String)> = Vec::new();
+    let mut movetext = String::new();
+
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            let (name, value) = parse_tag(line)?;
+            if tags.iter().any(|(other, _)| *other == name) {
+                return Err(NotationError::BadTag(format!("'{name}' is given twice")));
+            }
+            tags.push((name, value));
+        } else {
+            movetext.push_str(line);
+            movetext.push(' ');
+        }
+    }
+
+    let tag = |name: &str| -> Result<&str, NotationError> {
+        tags.iter()
+            .find(|(other, _)| other == name)
+            .map(|(_, value)| value.as_str())
+            .ok_or(NotationError::MissingTag(name.to_string()))
+    };
+    let bad_tag = |name: &str| NotationError::BadTag(format!("can't understand '{name}'"));
+
+    let size: FieldSize = tag("Size")?.parse().map_err(|_| bad_tag("Size"))?;
+    if size.width > MAX_FIELD_SIZE || size.height > MAX_FIELD_SIZE {
+        return Err(NotationError::BadTag(format!("unsupported field size {size}")));
+    }
+    let fleet = shape::parse_fleet(tag("Fleet")?).map_err(|_| bad_tag("Fleet"))?;
+    let touching = tag("Touching")?.parse().map_err(|_| bad_tag("Touching"))?;
+    let salvo = match tag("Salvo") {
+        Ok(salvo) => salvo.parse().map_err(|_| bad_tag("Salvo"))?,
+        Err(_) => Salvo::Off,
+    };
+    let turns = match tag("Turns") {
+        Ok(turns) => turns.parse().map_err(|_| bad_tag("Turns"))?,
+        Err(_) => TurnOrder::Alternate,
+    };
+    let arsenal = match tag("Arsenal") {
+        Ok(arsenal) => arsenal.parse().map_err(|_| bad_tag("Arsenal"))?,
+        Err(_) => Arsenal::default(),
+    };
+    let islands = match tag("Islands") {
+        Ok(islands) => terrain::parse_cells(islands, size).ok_or_else(|| bad_tag("Islands"))?,
+        Err(_) => Vec::new(),
+    };
+    let mines = match tag("Mines") {
+        Ok(mines) => mines.parse().map_err(|_| bad_tag("Mines"))?,
+        Err(_) => 0,
+    };
+    let expected_result = tag("Result")?.to_string();
+
+    let mut game = Game::new(Rules { size, fleet, touching, salvo, turns, arsenal, islands, mines });
+    if mines > 0 {
+        for (side, name) in [(Side::First, "Mines1"), (Side::Second, "Mines2")] {
+            for (x, y) in terrain::parse_cells(tag(name)?, size).ok_or_else(|| bad_tag(name))? {
+                game.handle(Command::PlaceMine(side, Shot { x, y }))
+                    .map_err(|error| NotationError::BadTag(format!("{side} can't hide a mine at {}: {error}", Shot { x, y })))?;
+            }
+        }
+    }
+    for (side, name) in [(Side::First, "Ships1"), (Side::Second, "Ships2")] {
+        for ship in tag(name)?.split_whitespace() {
+            let ship: Ship = ship.parse().map_err(|_| bad_tag(name))?;
+            game.handle(Command::PlaceShip(side, ship))
+                .map_err(|error| NotationError::IllegalShip(side, ship, error.to_string()))?;
+        }
+        if game.ships_to_place(side).is_empty() && game.mines_to_place(side) == 0 {
+            game.handle(Command::ConfirmFleet(side)).expect("the fleet is complete");
+        }
+    }
+    if game.phase() == Phase::Placement {
+        return Err(NotationError::BadTag("the fleets aren't complete".to_string()));
+    }
+
+    let mut number = 1;
+    let mut pair_open = false;
+    let mut numbered: Option<Side> = None;
+    let mut movetext_result = None;
+
+    for token in movetext.split_whitespace() {
+        if movetext_result.is_some() {
+            return Err(NotationError::BadMove(format!("'{token}' after the result")));
+        }
+
+        if let Some(declared) = token.strip_suffix("...") {
+            check_number(declared, number)?;
+            numbered = Some(Side::Second);
+        } else if let Some(declared) = token.strip_suffix('.') {
+            // Player 1 shooting twice in a row starts a new pair.
+            if pair_open {
+                number += 1;
+                pair_open = false;
+            }
+            check_number(declared, number)?;
+            numbered = Some(Side::First);
+        } else if matches!(token, "1-0" | "0-1" | "*") {
+            movetext_result = Some(token);
+        } else {
+            let side = match (numbered.take(), pair_open) {
+                (Some(side), _) => side,
+                (None, true) => Side::Second,
+                (None, false) => return Err(NotationError::BadMove(format!("'{token}' has no move number"))),
+            };
+            fire(&mut game, number, side, token)?;
+
+            match side {
+                Side::First => pair_open = true,
+                Side::Second => {
+                    pair_open = false;
+                    number += 1;
+                }
+            }
+        }
+    }
+
+    let actual_result = result(game.phase());
+    if let Some(movetext_result) = movetext_result {
+        if movetext_result != expected_result {
+            return Err(NotationError::BadMove(format!(
+                "the moves end in '{movetext_result}', but the result is '{expected_result}'"
+            )));
+        }
+    }
+    if actual_result != expected_result {
+        return Err(NotationError::WrongResult { claimed: expected_result, actual: actual_result.to_string() });
+    }
+
+    let players = [tag("Player1")?.to_string(), tag("Player2")?.to_string()];
+    let date = tag("Date")?.to_string();
+
+    Ok(NotatedGame { game, players, date })
+}
+
+/// Fires the volley of `token` and checks that every shot did what its
+/// mark says.
+fn fire(game: &mut Game, number: usize, side: Side, token: &str) -> Result<(), NotationError> {
+    if token.starts_with(['*', '>', '<', '?']) {
+        return fire_special(game, number, side, token);
+    }
+
+    let mut marked = Vec::new();
+    for part in token.split(',') {
+        let (shot, claimed) = match part.char_indices().last() {
+            Some((index, mark @ ('x' | '+' | '#' | '!'))) => (&part[..index], mark),
+            _ => return Err(NotationError::BadMove(format!("'{part}' doesn't say what the shot did"))),
+        };
+        let shot: Shot = shot.parse().map_err(|error| NotationError::BadMove(format!("'{part}': {error}")))?;
+
+        game.handle(Command::Shoot(side, shot)).map_err(|error| {
+            NotationError::IllegalMove { number, side, attack: Attack::Shot(shot), reason: error.to_string() }
+        })?;
+        marked.push((shot, claimed));
+    }
+    if !game.pending_shots().is_empty() {
+        return Err(NotationError::BadMove(format!("'{token}' isn't a whole volley")));
+    }
+
+    for (shot, claimed) in marked {
+        let actual = annotation(game.player(side).checked_field().at(shot.x, shot.y));
+        if claimed != actual {
+            return Err(NotationError::WrongAnnotation { number, side, attack: Attack::Shot(shot), claimed, actual });
+        }
+    }
+    Ok(())
+}
+
+/// Uses the special attack of `token` and checks that it did what its
+/// mark says.
+fn fire_special(game: &mut Game, number: usize, side: Side, token: &str) -> Result<(), NotationError> {
+    let bad = || NotationError::BadMove(format!("can't understand '{token}'"));
+    let (target, claimed) = match token.char_indices().last() {
+        Some((index, mark)) if index > 0 => (&token[1..index], mark),
+        _ => return Err(bad()),
+    };
+
+    let special = match token.chars().next() {
+        Some('*') => Special::Bomb(target.parse().map_err(|_| bad())?),
+        Some(arrow @ ('>' | '<')) => Special::Torpedo {
+            row: conversions::label_to_usize(target).ok_or_else(bad)?,
+            heading: if arrow == '>' { Heading::East } else { Heading::West },
+        },
+        _ => Special::Sonar(target.strip_suffix('=').ok_or_else(bad)?.parse().map_err(|_| bad())?),
+    };
+
+    let events = game.handle(Command::UseSpecial(side, special)).map_err(|error| {
+        NotationError::IllegalMove { number, side, attack: Attack::Special(special), reason: error.to_string() }
+    })?;
+    let actual = special_mark(special, &events);
+    if claimed != actual {
+        return Err(NotationError::WrongAnnotation { number, side, attack: Attack::Special(special), claimed, actual });
+    }
+    Ok(())
+}
+
+/// How a special attack is written, with what it did according to
+/// `events`.
+fn special_token(special: Special, events: &[Event]) -> String {
+    let mark = special_mark(special, events);
+    match special {
+        Special::Bomb(centre) => format!("*{centre}{mark}"),
+        Special::Torpedo { row, heading } => format!("{heading}{}{mark}", conversions::usize_to_label(row)),
+        Special::Sonar(centre) => format!("?{centre}={mark}"),
+    }
+}
+
+/// The best thing a bomb or torpedo did, or how many ship cells a sonar
+/// ping found. Setting off a mine only beats missing.
+fn special_mark(special: Special, events: &[Event]) -> char {
+    if let Special::Sonar(_) = special {
+        let found = events.iter().find_map(|event| match event {
+            Event::SonarPing { reading, .. } => Some(reading.ship_cells),
+            _ => None,
+        });
+        // The 3×3 square has at most 9 cells, so it's always one digit.
+        return char::from_digit(found.unwrap_or(0) as u32, 10).unwrap_or('?');
+    }
+
+    if events.iter().any(|event| matches!(event, Event::ShipSunk { .. })) {
+        '#'
+    } else if events.iter().any(|event| matches!(event, Event::Hit { .. })) {
+        '+'
+    } else if events.iter().any(|event| matches!(event, Event::MineHit { .. })) {
+        '!'
+    } else {
+        'x'
+    }
+}
+
+fn check_number(declared: &str, expected: usize) -> Result<(), NotationError> {
+    match declared.parse::<usize>() {
+        Ok(declared) if declared == expected => Ok(()),
+        _ => Err(NotationError::BadMove(format!("expected move number {expected}, got '{declared}'"))),
+    }
+}
+
+fn parse_tag(line: &str) -> Result<(String, String), NotationError> {
+    let bad = || NotationError::BadTag(format!("'{line}' should look like [Name \"value\"]"));
+
+    let inner = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')).ok_or_else(bad)?;
+    let (name, value) = inner.split_once(' ').ok_or_else(bad)?;
+    let value = value.trim().strip_prefix('"').and_then(|value| value.strip_suffix('"')).ok_or_else(bad)?;
+
+    Ok((name.to_string(), value.to_string()))
+}
+
+fn annotation(outcome: CheckedCell) -> char {
+    match outcome {
+        CheckedCell::Hit => '+',
+        CheckedCell::Kill => '#',
+        CheckedCell::Mine => '!',
+        _ => 'x',
+    }
+}
+
+fn result(phase: Phase) -> &'static str {
+    match phase {
+        Phase::Finished(Side::First) => "1-0",
+        Phase::Finished(Side::Second) => "0-1",
+        _ => "*",
+    }
+}
+
+/// Today as `YYYY.MM.DD`, like PGN dates.
+fn today() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs() / 86_400) as i64;
+
+    // Days since the epoch to a civil date, after Howard Hinnant's
+    // `civil_from_days`.
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}.{month:02}.{day:02}")
+}
+
+#[derive(Debug)]
+pub enum NotationError {
+    MissingTag(String),
+    BadTag(String),
+    IllegalShip(Side, Ship, String),
+    /// The movetext can't be read.
+    BadMove(String),
+    IllegalMove { number: usize, side: Side, attack: Attack, reason: String },
+    WrongAnnotation { number: usize, side: Side, attack: Attack, claimed: char, actual: char },
+    WrongResult { claimed: String, actual: String },
+}
+
+impl Display for NotationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotationError::MissingTag(name) => write!(f, "the '{name}' tag is missing"),
+            NotationError::BadTag(reason) => write!(f, "bad tag: {reason}"),
+            NotationError::IllegalShip(side, ship, reason) => write!(f, "{side} can't place {ship}: {reason}"),
+            NotationError::BadMove(reason) => write!(f, "bad move: {reason}"),
+            NotationError::IllegalMove { number, side, attack, reason } => {
+                write!(f, "move {number}: {side} can't fire {attack}: {reason}")
+            }
+            NotationError::WrongAnnotation { number, side, attack, claimed, actual } => {
+                write!(f, "move {number}: {side}'s {attack} is marked '{claimed}', but it was '{actual}'")
+            }
+            NotationError::WrongResult { claimed, actual } => {
+                write!(f, "the result is given as '{claimed}', but the moves end in '{actual}'")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battleships::{shape::Shape, ship::Rotation};
+
+    /// A game still being played, with every rule the tags have to keep.
+    fn unfinished_game() -> Game {
+        let rules = Rules {
+            size: FieldSize { width: 6, height: 5 },
+            fleet: vec![Shape::Line(3), Shape::Line(1)],
+            turns: TurnOrder::HitShootsAgain,
+            arsenal: Arsenal { bombs: 1, torpedoes: 1, sonars: 1 },
+            islands: vec![(5, 0)],
+            mines: 1,
+            ..Rules::default()
+        };
+        let mut game = Game::new(rules);
+        let first = [Ship::line(3, 0, 0, Rotation::Horizontal), Ship::line(1, 0, 2, Rotation::Horizontal)];
+        let second = [Ship::line(3, 0, 4, Rotation::Horizontal), Ship::line(1, 4, 2, Rotation::Horizontal)];
+        game.place_fleet_and_mines(Side::First, &first, &[Shot { x: 4, y: 4 }]).unwrap();
+        game.place_fleet_and_mines(Side::Second, &second, &[Shot { x: 2, y: 2 }]).unwrap();
+
+        for (side, attack) in [
+            (Side::First, Attack::Shot(Shot { x: 0, y: 4 })),
+            (Side::First, Attack::Special(Special::Sonar(Shot { x: 4, y: 2 }))),
+            (Side::Second, Attack::Special(Special::Bomb(Shot { x: 4, y: 1 }))),
+            (Side::First, Attack::Shot(Shot { x: 2, y: 2 })),
+            (Side::Second, Attack::Special(Special::Torpedo { row: 3, heading: Heading::West })),
+        ] {
+            game.handle(Command::attack(side, attack)).unwrap();
+        }
+        game
+    }
+
+    #[test]
+    fn round_trips_an_unfinished_game() {
+        let game = unfinished_game();
+        let text = export(&game, ["Alice", "Bob"]);
+        let imported = import(&text).unwrap();
+
+        assert_eq!(imported.players, ["Alice", "Bob"]);
+        assert_eq!(imported.game.phase(), game.phase());
+        assert_eq!(imported.game.history().len(), 5);
+        assert_eq!(export(&imported.game, ["Alice", "Bob"]), text);
+    }
+
+    #[test]
+    fn round_trips_a_finished_salvo_game() {
+        let rules = Rules {
+            size: FieldSize { width: 4, height: 4 },
+            fleet: vec![Shape::Line(2)],
+            salvo: Salvo::Fixed(2),
+            ..Rules::default()
+        };
+        let mut game = Game::new(rules);
+        game.place_fleet(Side::First, &[Ship::line(2, 0, 0, Rotation::Horizontal)]).unwrap();
+        game.place_fleet(Side::Second, &[Ship::line(2, 0, 3, Rotation::Horizontal)]).unwrap();
+        for (side, shot) in [(Side::First, (3, 3)), (Side::First, (0, 3)), (Side::Second, (0, 0)), (Side::Second, (2, 2))] {
+            game.handle(Command::attack(side, Attack::Shot(Shot { x: shot.0, y: shot.1 }))).unwrap();
+        }
+        for shot in [(3, 0), (1, 3)] {
+            game.handle(Command::attack(Side::First, Attack::Shot(Shot { x: shot.0, y: shot.1 }))).unwrap();
+        }
+        assert_eq!(game.phase(), Phase::Finished(Side::First));
+
+        let text = export(&game, ["Alice", "Bob"]);
+        assert!(text.contains("1-0"));
+        let imported = import(&text).unwrap();
+        assert_eq!(imported.game.phase(), Phase::Finished(Side::First));
+        assert_eq!(export(&imported.game, ["Alice", "Bob"]), text);
+    }
+
+    #[test]
+    fn refuses_fields_larger_than_any_game() {
+        let text = export(&unfinished_game(), ["Alice", "Bob"]).replace("[Size \"6x5\"]", "[Size \"18446744073709551615x5\"]");
+        assert!(matches!(import(&text), Err(NotationError::BadTag(_))));
+    }
+}
@@ -1,56 +1,206 @@
 use super::ship::{Ship};
+use super::shot::Shot;
 use super::field::{PlayerField, CheckField, FieldCell, CheckedCell, ShipPlacementError};
+use super::rules::TouchingPolicy;
+use super::weapons::{self, SonarReading};
+use crate::utilities::conversions;
+
+/// Shading for the heat map, from cold to hot.
+const HEAT_SHADES: [&str; 5] = ["[ ]", "[\u{2591}]", "[\u{2592}]", "[\u{2593}]", "[\u{2588}]"];
 
 pub enum Victory {
     Win,
     NotWin,
 }
 
+/// What a shot did to a fleet that isn't known here, as its owner told.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub enum Outcome {
+    Miss,
+    Hit,
+    /// The shot sunk this whole ship.
+    Sunk(Ship),
+    /// The shot set off a mine.
+    Mine,
+}
+
+/// Advice drawn over the tracking grid.
+#[derive(Default)]
+pub struct Overlay {
+    /// The cell recommended to shoot at.
+    pub highlight: Option<Shot>,
+    /// How likely every unchecked cell is to hide a ship, from `0.0` to
+    /// `1.0`, row by row.
+    pub heat: Option<Vec<f64>>,
+}
+
 pub struct Player {
     player_field: PlayerField,
     checked_field: CheckField,
-    size: usize,
+    ships: Vec<Ship>,
+    mines: Vec<Shot>,
+    width: usize,
+    height: usize,
 }
 
 impl Player {
-    pub fn new(player_field_size: usize) -> Player {
-        let player_field = PlayerField::new(player_field_size);
-        let checked_field = CheckField::new(player_field_size);
+    pub fn new(width: usize, height: usize) -> Player {
+        Player::with_touching(width, height, TouchingPolicy::NoTouch)
+    }
+
+    pub fn with_touching(width: usize, height: usize, touching: TouchingPolicy) -> Player {
+        let player_field = PlayerField::with_touching(width, height, touching);
+        let checked_field = CheckField::with_touching(width, height, touching);
 
         Player {
             player_field,
             checked_field,
-            size: player_field_size,
+            ships: Vec::new(),
+            mines: Vec::new(),
+            width,
+            height,
         }
     }
 
+    pub fn player_field(&self) -> &PlayerField {
+        &self.player_field
+    }
+
+    pub fn checked_field(&self) -> &CheckField {
+        &self.checked_field
+    }
+
+    /// Every ship placed so far, in placement order.
+    pub fn ships(&self) -> &[Ship] {
+        &self.ships
+    }
+
+    /// Every mine placed so far, in placement order.
+    pub fn mines(&self) -> &[Shot] {
+        &self.mines
+    }
+
+    /// Puts an island on `(x, y)` of both fields, since everyone knows
+    /// where the land is.
+    pub fn add_island(&mut self, x: usize, y: usize) {
+        self.player_field.set(x, y, FieldCell::Island);
+        self.checked_field.mark(x, y, CheckedCell::Island);
+    }
+
+    /// Lets the fleet have shaped ships, which changes what a hit on
+    /// them says about the cells around it.
+    pub fn allow_bent_ships(&mut self) {
+        self.checked_field.allow_bent_ships();
+    }
+
+    /// The ship covering `(x, y)`, if there is one.
+    pub fn ship_at(&self, x: usize, y: usize) -> Option<&Ship> {
+        self.ships.iter().find(|ship| ship.cells().any(|cell| cell == (x, y)))
+    }
+
+    /// How many ships still have a cell that wasn't hit.
+    pub fn ships_afloat(&self) -> usize {
+        self.ships.iter()
+            .filter(|ship| ship.cells().any(|(x, y)| self.player_field.at(x, y) == FieldCell::Ship))
+            .count()
+    }
+
     pub fn did_lose(&self) -> bool {
-        for x in 0..self.size {
-            for y in 0..self.size {
-                if self.player_field.is_ship_on(x, y) {
-                    return false;
-                }
-            }
-        }
-        true
+        !self.player_field.has_ships_left()
     }
 
     pub fn place_ship(&mut self, ship: &Ship) {
         self.player_field.place_ship(ship);
+        self.ships.push(*ship);
+    }
+
+    /// Takes `ship` off the field. Returns `false` if it wasn't placed.
+    pub fn remove_ship(&mut self, ship: &Ship) -> bool {
+        match self.ships.iter().position(|placed| placed == ship) {
+            Some(index) => {
+                self.ships.remove(index);
+                self.player_field.remove_ship(ship);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Puts the ship at `index` of `ships` at `to` instead, keeping its
+    /// place in the list. Checked without the ship itself, so it can
+    /// overlap its old spot. Nothing changes if `to` doesn't fit.
+    pub fn replace_ship(&mut self, index: usize, to: &Ship) -> Result<(), ShipPlacementError> {
+        let from = self.ships[index];
+        self.player_field.remove_ship(&from);
+        if let Err(error) = self.player_field.can_place(to) {
+            self.player_field.place_ship(&from);
+            return Err(error);
+        }
+        self.player_field.place_ship(to);
+        self.ships[index] = *to;
+        Ok(())
     }
 
     pub fn can_place(&self, ship: &Ship) -> Result<(), ShipPlacementError> {
         self.player_field.can_place(ship)
     }
 
+    /// Mines go on open water, even right next to a ship.
+    pub fn can_place_mine(&self, mine: Shot) -> Result<(), ShipPlacementError> {
+        let Shot { x, y } = mine;
+        if x >= self.width {
+            return Err(ShipPlacementError::OutOfBoundsX(self.width));
+        }
+        if y >= self.height {
+            return Err(ShipPlacementError::OutOfBoundsY(self.height));
+        }
+
+        match self.player_field.at(x, y) {
+            FieldCell::Island => Err(ShipPlacementError::OnIsland(x, y)),
+            FieldCell::Mine => Err(ShipPlacementError::OnMine(x, y)),
+            FieldCell::Ship => Err(ShipPlacementError::OnShip(x, y)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn place_mine(&mut self, mine: Shot) {
+        debug_assert!(self.can_place_mine(mine).is_ok());
+        self.player_field.set(mine.x, mine.y, FieldCell::Mine);
+        self.mines.push(mine);
+    }
+
+    /// Takes the mine at `mine` off the field. Returns `false` if there
+    /// was none.
+    pub fn remove_mine(&mut self, mine: Shot) -> bool {
+        match self.mines.iter().position(|placed| placed.x == mine.x && placed.y == mine.y) {
+            Some(index) => {
+                self.mines.remove(index);
+                self.player_field.set(mine.x, mine.y, FieldCell::Empty);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The ship cell that wasn't hit yet closest to `(x, y)`, where a
+    /// mine went off. Ties go to the upper, then the left one.
+    pub fn nearest_ship_cell(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        (0..self.height)
+            .flat_map(|ship_y| (0..self.width).map(move |ship_x| (ship_x, ship_y)))
+            .filter(|&(ship_x, ship_y)| self.player_field.at(ship_x, ship_y) == FieldCell::Ship)
+            .min_by_key(|&(ship_x, ship_y)| x.abs_diff(ship_x).pow(2) + y.abs_diff(ship_y).pow(2))
+    }
+
     pub fn take_damage(&mut self, x: usize, y: usize) -> Result<CheckedCell, String> {
         let shot_cell = self.player_field.at(x, y);
         match shot_cell {
             FieldCell::Ship => {
-                if !self.player_field.will_ship_survive_after_shot(x, y) {
-                    self.player_field.mark_dead(x, y);
+                let ship = *self.ship_at(x, y).expect("every ship cell belongs to a placed ship");
+                if !self.player_field.will_ship_survive_after_shot(&ship, x, y) {
+                    self.player_field.mark_dead(&ship);
                     return Ok(CheckedCell::Kill);
-                }            
+                }
 
                 self.player_field.mark_hit(x, y);
                 Ok(CheckedCell::Hit)
@@ -58,15 +208,30 @@ impl Player {
             FieldCell::EnemyMiss => Err("Already shot in this place".to_string()),
             FieldCell::Hit => Err("Already shot in this place!".to_string()),
             FieldCell::Dead => Err("Already shot in this place!".to_string()),
+            FieldCell::Crater => Err("Already shot in this place!".to_string()),
+            FieldCell::Island => Err("There's an island in this place!".to_string()),
             FieldCell::Empty => { 
                 self.player_field.mark_enemy_miss(x, y);
                 Ok(CheckedCell::Miss)
             }
+            FieldCell::Mine => {
+                self.player_field.set(x, y, FieldCell::Crater);
+                Ok(CheckedCell::Mine)
+            }
         }
     }
 
     pub fn shoot(&mut self, opponent: &mut Player, x: usize, y: usize) -> Result<Victory, String> {
         match opponent.take_damage(x, y) {
+            Ok(CheckedCell::Kill) => {
+                let ship = opponent.ship_at(x, y).expect("a sunk ship was placed");
+                self.checked_field.mark_sunk(ship);
+                if opponent.did_lose() {
+                    Ok(Victory::Win)
+                } else {
+                    Ok(Victory::NotWin)
+                }
+            },
             Ok(hit_result) => { 
                 self.checked_field.mark(x, y, hit_result);
                 if opponent.did_lose() {
@@ -79,22 +244,87 @@ impl Player {
         }
     }
 
+    /// Marks what a shot at `(x, y)` did to `opponent`, whose fleet isn't
+    /// known here, as they told. A sunk ship becomes known and lies on
+    /// their field as a wreck. Fails if it can't have been like that.
+    pub fn record_shot(&mut self, opponent: &mut Player, x: usize, y: usize, outcome: Outcome) -> Result<(), String> {
+        if opponent.player_field.at(x, y) != FieldCell::Empty {
+            return Err("Already shot in this place!".to_string());
+        }
+
+        match outcome {
+            Outcome::Miss => {
+                opponent.player_field.mark_enemy_miss(x, y);
+                self.checked_field.mark(x, y, CheckedCell::Miss);
+            }
+            Outcome::Hit => {
+                opponent.player_field.mark_hit(x, y);
+                self.checked_field.mark(x, y, CheckedCell::Hit);
+            }
+            Outcome::Mine => {
+                opponent.player_field.set(x, y, FieldCell::Crater);
+                self.checked_field.mark(x, y, CheckedCell::Mine);
+            }
+            Outcome::Sunk(ship) => {
+                // Every other cell of it must have been hit before.
+                let wreck = ship.cells().all(|(ship_x, ship_y)| {
+                    (ship_x, ship_y) == (x, y)
+                        || ship_x < self.width && ship_y < self.height && opponent.player_field.at(ship_x, ship_y) == FieldCell::Hit
+                });
+                if !wreck || !ship.cells().any(|cell| cell == (x, y)) {
+                    return Err(format!("the ship {ship} can't have sunk at {}", Shot { x, y }));
+                }
+                opponent.player_field.mark_dead(&ship);
+                opponent.ships.push(ship);
+                self.checked_field.mark_sunk(&ship);
+            }
+        }
+        Ok(())
+    }
+
+    /// How many cells around `centre` hold a ship, hit or not.
+    pub fn ship_cells_around(&self, centre: Shot) -> usize {
+        weapons::area(centre, self.width, self.height)
+            .filter(|&(x, y)| matches!(self.player_field.at(x, y), FieldCell::Ship | FieldCell::Hit | FieldCell::Dead))
+            .count()
+    }
+
+    /// Counts the ship cells of `opponent` around `centre` and remembers
+    /// what was found.
+    pub fn ping(&mut self, opponent: &Player, centre: Shot) -> SonarReading {
+        let reading = SonarReading { centre, ship_cells: opponent.ship_cells_around(centre) };
+        self.checked_field.add_sonar_reading(reading);
+        reading
+    }
+
+    /// Remembers a sonar reading the owner of a hidden fleet told.
+    pub fn record_ping(&mut self, reading: SonarReading) {
+        self.checked_field.add_sonar_reading(reading);
+    }
+
     pub fn print(&self) {
-        let chars: Vec<char> = ('A'..'Z').take(self.size).collect();
+        self.print_with_overlay(&Overlay::default());
+    }
+
+    pub fn print_with_overlay(&self, overlay: &Overlay) {
         let field_separator = "\t";
+        // Labels and numbers are padded, so that fields with more than
+        // 26 rows or 9 columns still line up.
+        let label_width = conversions::usize_to_label(self.height - 1).len();
+        let margin = " ".repeat(label_width + 2);
 
-        let number_header: String = (1..=self.size)
-                .map(|x| format!(" {x} "))
+        let number_header: String = (1..=self.width)
+                .map(|x| format!("{x:^3}"))
                 .collect();
         let line = format!(
-            "   {number_header}{field_separator}   {number_header} "
+            "{margin}{number_header}{field_separator}{margin}{number_header} "
         );
         println!("{line}");
 
-        for y in 0..self.size {
-            let marker = chars[y];
+        for y in 0..self.height {
+            let marker = format!("{:>label_width$}", conversions::usize_to_label(y));
 
-            let player_field_line: String = (0..self.size)
+            let player_field_line: String = (0..self.width)
                     .map(|x| self.player_field.at(x, y))
                     .map(|cell| match cell {
                         FieldCell::Empty => "[ ]",
@@ -102,16 +332,28 @@ impl Player {
                         FieldCell::EnemyMiss => "[*]",
                         FieldCell::Hit => "[!]",
                         FieldCell::Dead => "[X]",
+                        FieldCell::Island => "[^]",
+                        FieldCell::Mine => "[+]",
+                        FieldCell::Crater => "[%]",
                     })
                     .collect();
 
-            let checked_field_line: String = (0..self.size)
-                    .map(|x| self.checked_field.at(x, y))
-                    .map(|cell| match cell {
-                        CheckedCell::Unchecked => "[ ]",
+            let checked_field_line: String = (0..self.width)
+                    .map(|x| match self.checked_field.at(x, y) {
+                        _ if overlay.highlight.is_some_and(|shot| shot.x == x && shot.y == y) => "[@]",
+                        CheckedCell::Unchecked => match &overlay.heat {
+                            Some(heat) => {
+                                let shade = (heat[y * self.width + x] * 4.0).ceil() as usize;
+                                HEAT_SHADES[shade.min(HEAT_SHADES.len() - 1)]
+                            }
+                            None => "[ ]",
+                        },
                         CheckedCell::Miss => "[*]",
                         CheckedCell::Hit => "[!]",
                         CheckedCell::Kill => "[X]",
+                        CheckedCell::KnownEmpty => "[.]",
+                        CheckedCell::Island => "[^]",
+                        CheckedCell::Mine => "[%]",
                     })
                     .collect();
             
@@ -0,0 +1,548 @@
+//! Recorded games. A replay is a plain text move list, written while the
+//! game is played:
+//!
+//! ```text
+//! version 1
+//! size 10
+//! fleet 4 3 3 2 2 2 1 1 1 1
+//! touching no-touch
+//! salvo 3
+//! turns hit-again
+//! arsenal 1,2,1
+//! islands E:5 E:6
+//! mines 2
+//! started 1760000000
+//! ship 1 4:A:1:h
+//! mine 1 C:7
+//! ...
+//! shot 1 A:5 5120
+//! special 2 torpedo C > 9870
+//! ...
+//! ```
+//!
+//! `started` is when the game began, in seconds since the Unix epoch.
+//! Ships are `<side> shape:y:x:rotation` and shots are `<side> y:x` and
+//! the milliseconds since the start. The shots of a volley follow each
+//! other. Special attacks are `<side> <attack>` and the time, like
+//! `special 1 bomb B:3 1200`. The `salvo` line is left out when there's
+//! one shot a turn, the `turns` line when the players simply take turns,
+//! the `arsenal` line when there are no special attacks and the `islands`
+//! and `mines` lines when there's no terrain.
+//! Shots taken before a saved game was resumed have no known time and are
+//! recorded at `0`. Changes to the fleet are `remove <side> <ship>`,
+//! `move <side> <from> <to>`, `clear <side>`, `mine <side> <cell>`,
+//! `unmine <side> <cell>` and `confirm <side>`, and a line with just
+//! `undo` takes the line before it back.
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+    game::{Game, Side, Phase, Command, GameError},
+    events::{Event, Observer},
+    game_constants::MAX_FIELD_SIZE,
+    notation::{self, NotationError},
+    rules::{Rules, FieldSize, Salvo, TurnOrder},
+    shape,
+    ship::Ship,
+    shot::Shot,
+    terrain,
+    weapons::{Arsenal, Attack, Special},
+};
+
+const REPLAY_DIRECTORY: &str = "replays";
+const REPLAY_VERSION: u32 = 1;
+
+/// One turn of a recorded game: a single shot, a whole volley when
+/// playing with salvos, or a special attack.
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Move {
+    pub side: Side,
+    pub attacks: Vec<Attack>,
+    /// When the last attack was fired, since the start of the game.
+    pub elapsed: Duration,
+}
+
+pub struct Replay {
+    rules: Rules,
+    started: u64,
+    ships: [Vec<Ship>; 2],
+    mines: [Vec<Shot>; 2],
+    moves: Vec<Move>,
+}
+
+impl Replay {
+    /// Reads a replay, or a game written down in the notation.
+    pub fn load(path: impl AsRef<Path>) -> Result<Replay, ReplayError> {
+        let contents = fs::read_to_string(path).map_err(ReplayError::IOError)?;
+
+        if contents.trim_start().starts_with('[') {
+            let notated = notation::import(&contents).map_err(ReplayError::Notation)?;
+            return Ok(Replay::from_game(&notated.game));
+        }
+        Replay::parse(&contents)
+    }
+
+    /// The replay of everything that happened in `game`. The time of the
+    /// shots isn't known, so they are all at the start.
+    pub fn from_game(game: &Game) -> Replay {
+        let mut replay = Replay {
+            rules: game.rules().clone(),
+            started: 0,
+            ships: [game.player(Side::First).ships().to_vec(), game.player(Side::Second).ships().to_vec()],
+            mines: [game.player(Side::First).mines().to_vec(), game.player(Side::Second).mines().to_vec()],
+            moves: Vec::new(),
+        };
+
+        let mut replayed = replay.game_at(0);
+        for &(side, attack) in game.history() {
+            replay.add_attack(&replayed, side, attack, Duration::ZERO);
+            replayed.handle(Command::attack(side, attack)).expect("the game was played");
+        }
+        replay
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// When the game began, in seconds since the Unix epoch.
+    pub fn started(&self) -> u64 {
+        self.started
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// The game as it was after the first `moves` moves.
+    pub fn game_at(&self, moves: usize) -> Game {
+        let mut game = Game::new(self.rules.clone());
+        for side in [Side::First, Side::Second] {
+            for mine in &self.mines[side.index()] {
+                game.handle(Command::PlaceMine(side, *mine)).expect("checked when loading");
+            }
+            for ship in &self.ships[side.index()] {
+                game.handle(Command::PlaceShip(side, *ship)).expect("checked when loading");
+            }
+            if game.ships_to_place(side).is_empty() && game.mines_to_place(side) == 0 {
+                game.handle(Command::ConfirmFleet(side)).expect("the fleet is complete");
+            }
+        }
+        for step in &self.moves[..moves.min(self.moves.len())] {
+            for &attack in &step.attacks {
+                game.handle(Command::attack(step.side, attack)).expect("checked when loading");
+            }
+        }
+        game
+    }
+
+    /// Adds an attack fired in `game`, to the volley it's in the middle
+    /// of or as a new move.
+    fn add_attack(&mut self, game: &Game, side: Side, attack: Attack, elapsed: Duration) {
+        match self.moves.last_mut() {
+            Some(last) if !game.pending_shots().is_empty() => {
+                last.attacks.push(attack);
+                last.elapsed = elapsed;
+            }
+            _ => self.moves.push(Move { side, attacks: vec![attack], elapsed }),
+        }
+    }
+
+    fn parse(contents: &str) -> Result<Replay, ReplayError> {
+        let mut lines = contents.lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with('#') && !line.is_empty())
+            .peekable();
+
+        let version = next_header(&mut lines, "version")?;
+        if version != REPLAY_VERSION.to_string() {
+            return Err(ReplayError::UnknownVersion(version));
+        }
+        let size = next_header(&mut lines, "size")?;
+        let size: FieldSize = size.parse().map_err(|_| ReplayError::Corrupt(format!("bad field size '{size}'")))?;
+        if size.width > MAX_FIELD_SIZE || size.height > MAX_FIELD_SIZE {
+            return Err(ReplayError::Corrupt(format!("unsupported field size {size}")));
+        }
+        let fleet = next_header(&mut lines, "fleet")?;
+        let fleet = shape::parse_fleet(&fleet).map_err(|error| ReplayError::Corrupt(format!("bad fleet: {error}")))?;
+        let touching = next_header(&mut lines, "touching")?;
+        let touching = touching.parse().map_err(|_| ReplayError::Corrupt(format!("unknown touching policy '{touching}'")))?;
+        let salvo = match lines.peek().and_then(|line| header_value(line, "salvo")) {
+            Some(salvo) => {
+                lines.next();
+                salvo.parse().map_err(|_| ReplayError::Corrupt(format!("bad salvo '{salvo}'")))?
+            }
+            None => Salvo::Off,
+        };
+        let turns = match lines.peek().and_then(|line| header_value(line, "turns")) {
+            Some(turns) => {
+                lines.next();
+                turns.parse().map_err(|_| ReplayError::Corrupt(format!("bad turns '{turns}'")))?
+            }
+            None => TurnOrder::Alternate,
+        };
+        let arsenal = match lines.peek().and_then(|line| header_value(line, "arsenal")) {
+            Some(arsenal) => {
+                lines.next();
+                arsenal.parse().map_err(|_| ReplayError::Corrupt(format!("bad arsenal '{arsenal}'")))?
+            }
+            None => Arsenal::default(),
+        };
+        let islands = match lines.peek().and_then(|line| header_value(line, "islands")) {
+            Some(islands) => {
+                lines.next();
+                terrain::parse_cells(&islands, size).ok_or(ReplayError::Corrupt(format!("bad islands '{islands}'")))?
+            }
+            None => Vec::new(),
+        };
+        let mines = match lines.peek().and_then(|line| header_value(line, "mines")) {
+            Some(mines) => {
+                lines.next();
+                parse_number(&mines)?
+            }
+            None => 0,
+        };
+        let started = next_header(&mut lines, "started")?;
+        let started = started.parse().map_err(|_| ReplayError::Corrupt(format!("bad start time '{started}'")))?;
+
+        let mut replay = Replay {
+            rules: Rules { size, fleet, touching, salvo, turns, arsenal, islands, mines },
+            started,
+            ships: [Vec::new(), Vec::new()],
+            mines: [Vec::new(), Vec::new()],
+            moves: Vec::new(),
+        };
+        // Played along, so that a broken replay is caught now and not in
+        // the middle of watching it.
+        let mut game = Game::new(replay.rules.clone());
+
+        // Whether every line that can be taken back was an attack.
+        let mut done: Vec<bool> = Vec::new();
+
+        for line in lines {
+            let corrupt = || ReplayError::Corrupt(format!("bad line '{line}'"));
+            let impossible = |error: GameError| ReplayError::Impossible(format!("'{line}': {error}"));
+            let mut words = line.split_whitespace();
+
+            let kind = words.next().ok_or_else(corrupt)?;
+            if kind == "undo" {
+                game.undo().map_err(impossible)?;
+                if done.pop() == Some(true) {
+                    let last = replay.moves.last_mut().expect("an attack was taken back");
+                    last.attacks.pop();
+                    if last.attacks.is_empty() {
+                        replay.moves.pop();
+                    }
+                }
+                continue;
+            }
+
+            let side = match words.next() {
+                Some("1") => Side::First,
+                Some("2") => Side::Second,
+                _ => return Err(corrupt()),
+            };
+            let mut ship = || -> Result<Ship, ReplayError> {
+                words.next().ok_or_else(corrupt)?.parse().map_err(|_| corrupt())
+            };
+
+            let command = match kind {
+                "ship" => Command::PlaceShip(side, ship()?),
+                "remove" => Command::RemoveShip(side, ship()?),
+                "move" => Command::MoveShip { side, from: ship()?, to: ship()? },
+                "clear" => Command::ClearFleet(side),
+                "mine" | "unmine" => {
+                    let mine: Shot = words.next().ok_or_else(corrupt)?.parse().map_err(|_| corrupt())?;
+                    if kind == "mine" { Command::PlaceMine(side, mine) } else { Command::RemoveMine(side, mine) }
+                }
+                "confirm" => Command::ConfirmFleet(side),
+                "shot" => {
+                    let shot: Shot = words.next().ok_or_else(corrupt)?.parse().map_err(|_| corrupt())?;
+                    let elapsed = words.next().ok_or_else(corrupt)?.parse().map_err(|_| corrupt())?;
+                    replay.add_attack(&game, side, Attack::Shot(shot), Duration::from_millis(elapsed));
+                    Command::Shoot(side, shot)
+                }
+                "special" => {
+                    let rest: Vec<&str> = words.collect();
+                    let (elapsed, special) = rest.split_last().ok_or_else(corrupt)?;
+                    let special: Special = special.join(" ").parse().map_err(|_| corrupt())?;
+                    let elapsed = elapsed.parse().map_err(|_| corrupt())?;
+                    replay.add_attack(&game, side, Attack::Special(special), Duration::from_millis(elapsed));
+                    Command::UseSpecial(side, special)
+                }
+                _ => return Err(corrupt()),
+            };
+            game.handle(command).map_err(impossible)?;
+            done.push(command.is_attack());
+        }
+
+        // Only the fleets the game was played with matter to the viewer,
+        // not how they came to be.
+        replay.ships = [game.player(Side::First).ships().to_vec(), game.player(Side::Second).ships().to_vec()];
+        replay.mines = [game.player(Side::First).mines().to_vec(), game.player(Side::Second).mines().to_vec()];
+
+        Ok(replay)
+    }
+}
+
+fn next_header<'a>(lines: &mut impl Iterator<Item = &'a str>, name: &str) -> Result<String, ReplayError> {
+    let line = lines.next().ok_or(ReplayError::Corrupt(format!("missing '{name}'")))?;
+    header_value(line, name).ok_or(ReplayError::Corrupt(format!("expected '{name}', got '{line}'")))
+}
+
+/// The rest of a header line that starts with `name`.
+fn header_value(line: &str, name: &str) -> Option<String> {
+    line.strip_prefix(name)
+        .filter(|rest| rest.starts_with(' '))
+        .map(|rest| rest.trim().to_string())
+}
+
+fn parse_number(text: &str) -> Result<usize, ReplayError> {
+    text.parse().map_err(|_| ReplayError::Corrupt(format!("bad number '{text}'")))
+}
+
+/// Writes the game as it is being played, usually to a new file in
+/// `replays/`.
+pub struct Recorder<W: Write> {
+    output: W,
+    start: Instant,
+}
+
+impl Recorder<File> {
+    /// Starts recording `game`, including whatever already happened in it.
+    pub fn start(game: &Game) -> io::Result<Recorder<File>> {
+        Recorder::start_played(game, SystemTime::now(), &[])
+    }
+
+    /// Starts recording `game`, which began at `began` without being
+    /// recorded. The attacks in its history were fired `times` after
+    /// that, and those past the end of `times` at `0`.
+    pub fn start_played(game: &Game, began: SystemTime, times: &[Duration]) -> io::Result<Recorder<File>> {
+        let path = PathBuf::from(REPLAY_DIRECTORY).join(format!("{}-{}.txt", unix_seconds(began), std::process::id()));
+
+        fs::create_dir_all(REPLAY_DIRECTORY)?;
+        Recorder::new(File::create(path)?, game, began, times)
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    /// Starts recording `game` to `output`, like `start_played`.
+    pub fn new(mut output: W, game: &Game, began: SystemTime, times: &[Duration]) -> io::Result<Recorder<W>> {
+        let started = unix_seconds(began);
+        let rules = game.rules();
+        writeln!(output, "# battleships replay")?;
+        writeln!(output, "version {REPLAY_VERSION}")?;
+        writeln!(output, "size {}", rules.size)?;
+        writeln!(output, "fleet {}", shape::format_fleet(&rules.fleet))?;
+        writeln!(output, "touching {}", rules.touching)?;
+        if rules.salvo != Salvo::Off {
+            writeln!(output, "salvo {}", rules.salvo)?;
+        }
+        if rules.turns != TurnOrder::Alternate {
+            writeln!(output, "turns {}", rules.turns)?;
+        }
+        if !rules.arsenal.is_empty() {
+            writeln!(output, "arsenal {}", rules.arsenal)?;
+        }
+        if !rules.islands.is_empty() {
+            writeln!(output, "islands {}", terrain::format_cells(rules.islands.iter().copied()))?;
+        }
+        if rules.mines > 0 {
+            writeln!(output, "mines {}", rules.mines)?;
+        }
+        writeln!(output, "started {started}")?;
+
+        for side in [Side::First, Side::Second] {
+            for mine in game.player(side).mines() {
+                writeln!(output, "mine {} {mine}", side.index() + 1)?;
+            }
+            for ship in game.player(side).ships() {
+                writeln!(output, "ship {} {ship}", side.index() + 1)?;
+            }
+            if game.is_fleet_confirmed(side) {
+                writeln!(output, "confirm {}", side.index() + 1)?;
+            }
+        }
+        for (index, (side, attack)) in game.history().iter().enumerate() {
+            let elapsed = times.get(index).map_or(0, Duration::as_millis);
+            match attack {
+                Attack::Shot(shot) => writeln!(output, "shot {} {shot} {elapsed}", side.index() + 1)?,
+                Attack::Special(special) => writeln!(output, "special {} {special} {elapsed}", side.index() + 1)?,
+            }
+        }
+        if let Phase::Shooting(side) = game.phase() {
+            for shot in game.pending_shots() {
+                writeln!(output, "shot {} {shot} 0", side.index() + 1)?;
+            }
+        }
+
+        let start = began.elapsed().ok().and_then(|ago| Instant::now().checked_sub(ago));
+        Ok(Recorder { output, start: start.unwrap_or_else(Instant::now) })
+    }
+}
+
+/// Seconds since the Unix epoch, or `0` for times before it.
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs())
+}
+
+impl<W: Write> Observer for Recorder<W> {
+    fn notify(&mut self, event: &Event) {
+        // Like the log, a replay that can't be written must not stop the game.
+        let _ = match event {
+            Event::ShipPlaced(side, ship) => writeln!(self.output, "ship {} {ship}", side.index() + 1),
+            Event::ShipRemoved(side, ship) => writeln!(self.output, "remove {} {ship}", side.index() + 1),
+            Event::ShipMoved { side, from, to } => writeln!(self.output, "move {} {from} {to}", side.index() + 1),
+            Event::FleetCleared(side) => writeln!(self.output, "clear {}", side.index() + 1),
+            Event::MinePlaced(side, mine) => writeln!(self.output, "mine {} {mine}", side.index() + 1),
+            Event::MineRemoved(side, mine) => writeln!(self.output, "unmine {} {mine}", side.index() + 1),
+            Event::FleetPlaced(side) => writeln!(self.output, "confirm {}", side.index() + 1),
+            Event::ShotFired { side, shot } => {
+                let elapsed = self.start.elapsed().as_millis();
+                writeln!(self.output, "shot {} {shot} {elapsed}", side.index() + 1)
+            }
+            Event::SpecialUsed { side, special } => {
+                let elapsed = self.start.elapsed().as_millis();
+                writeln!(self.output, "special {} {special} {elapsed}", side.index() + 1)
+            }
+            Event::Undone(_) => writeln!(self.output, "undo"),
+            _ => Ok(()),
+        };
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    IOError(io::Error),
+    UnknownVersion(String),
+    Corrupt(String),
+    /// The moves break the rules of the game.
+    Impossible(String),
+    Notation(NotationError),
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::IOError(inner) => write!(f, "can't read the replay: {inner}"),
+            ReplayError::UnknownVersion(version) => write!(f, "unknown replay version {version}"),
+            ReplayError::Corrupt(reason) => write!(f, "the replay is corrupt: {reason}"),
+            ReplayError::Impossible(reason) => write!(f, "the replay is impossible: {reason}"),
+            ReplayError::Notation(inner) => write!(f, "can't read the game: {inner}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::battleships::{rules::FieldSize, save, shape::Shape, ship::Rotation};
+
+    /// Somewhere to record to that can still be read once the recorder
+    /// belongs to the game.
+    #[derive(Clone)]
+    #[derive(Default)]
+    struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    fn rules() -> Rules {
+        Rules {
+            size: FieldSize { width: 6, height: 5 },
+            fleet: vec![Shape::Line(3), Shape::Line(1)],
+            salvo: Salvo::Fixed(2),
+            arsenal: Arsenal { bombs: 1, torpedoes: 0, sonars: 0 },
+            islands: vec![(5, 0)],
+            mines: 1,
+            ..Rules::default()
+        }
+    }
+
+    #[test]
+    fn round_trips_a_recorded_game() {
+        let mut game = Game::new(rules());
+        let buffer = Buffer::default();
+        game.subscribe(Box::new(Recorder::new(buffer.clone(), &game, SystemTime::now(), &[]).unwrap()));
+
+        let first = Ship::line(3, 0, 0, Rotation::Horizontal);
+        for command in [
+            Command::PlaceShip(Side::First, first),
+            Command::MoveShip { side: Side::First, from: first, to: Ship { y: 1, ..first } },
+            Command::PlaceShip(Side::First, Ship::line(1, 4, 4, Rotation::Horizontal)),
+            Command::PlaceMine(Side::First, Shot { x: 0, y: 4 }),
+            Command::ConfirmFleet(Side::First),
+            Command::PlaceShip(Side::Second, Ship::line(3, 1, 3, Rotation::Horizontal)),
+            Command::PlaceShip(Side::Second, Ship::line(1, 0, 0, Rotation::Horizontal)),
+            Command::PlaceMine(Side::Second, Shot { x: 3, y: 1 }),
+            Command::ConfirmFleet(Side::Second),
+            Command::Shoot(Side::First, Shot { x: 1, y: 3 }),
+            Command::Shoot(Side::First, Shot { x: 0, y: 0 }),
+            Command::UseSpecial(Side::Second, Special::Bomb(Shot { x: 1, y: 1 })),
+            Command::Shoot(Side::First, Shot { x: 2, y: 3 }),
+        ] {
+            game.handle(command).unwrap();
+        }
+        game.undo().unwrap();
+        game.handle(Command::Shoot(Side::First, Shot { x: 3, y: 3 })).unwrap();
+
+        let replay = Replay::parse(&buffer.text()).unwrap();
+        assert_eq!(replay.rules().size, rules().size);
+        assert_eq!(replay.moves().len(), 3);
+        assert!(matches!(replay.moves()[2].attacks[..], [Attack::Shot(Shot { x: 3, y: 3 })]));
+        assert_eq!(save::to_text(&replay.game_at(replay.moves().len()), None), save::to_text(&game, None));
+    }
+
+    #[test]
+    fn keeps_the_times_of_a_game_played_before_recording() {
+        let mut game = Game::new(Rules { salvo: Salvo::Off, mines: 0, ..rules() });
+        let first = [Ship::line(3, 0, 0, Rotation::Horizontal), Ship::line(1, 0, 2, Rotation::Horizontal)];
+        let second = [Ship::line(3, 0, 4, Rotation::Horizontal), Ship::line(1, 4, 2, Rotation::Horizontal)];
+        game.place_fleet(Side::First, &first).unwrap();
+        game.place_fleet(Side::Second, &second).unwrap();
+        for (side, shot) in [(Side::First, Shot { x: 5, y: 4 }), (Side::Second, Shot { x: 3, y: 3 }), (Side::First, Shot { x: 0, y: 4 })] {
+            game.handle(Command::Shoot(side, shot)).unwrap();
+        }
+
+        let buffer = Buffer::default();
+        let times = [Duration::from_millis(1500), Duration::from_millis(4200)];
+        Recorder::new(buffer.clone(), &game, UNIX_EPOCH + Duration::from_secs(1_760_000_000), &times).unwrap();
+
+        let replay = Replay::parse(&buffer.text()).unwrap();
+        assert_eq!(replay.started(), 1_760_000_000);
+        let elapsed: Vec<Duration> = replay.moves().iter().map(|step| step.elapsed).collect();
+        assert_eq!(elapsed, [times[0], times[1], Duration::ZERO]);
+    }
+
+    #[test]
+    fn refuses_fields_larger_than_any_game() {
+        let game = Game::new(rules());
+        let buffer = Buffer::default();
+        Recorder::new(buffer.clone(), &game, SystemTime::now(), &[]).unwrap();
+
+        let text = buffer.text().replace("size 6x5", "size 18446744073709551615x5");
+        assert!(matches!(Replay::parse(&text), Err(ReplayError::Corrupt(_))));
+    }
+}
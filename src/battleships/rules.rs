@@ -0,0 +1,277 @@
+use std::{fmt::Display, str::FromStr};
+
+use super::{
+    game_constants::{FIELD_SIZE, FLEET, MAX_SALVO},
+    shape::Shape,
+    weapons::Arsenal,
+};
+
+const DIAGONALS: [(isize, isize); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+const EDGES: [(isize, isize); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+const NEIGHBOURS: [(isize, isize); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+/// How close ships may be placed to each other.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum TouchingPolicy {
+    /// Ships can't touch at all, not even with corners, as in the Russian
+    /// "Морской бой".
+    NoTouch,
+    /// Ships may meet at their corners, but never share an edge.
+    CornersOnly,
+    /// Ships may be right next to each other, as in the Hasbro rules.
+    Anything,
+}
+
+impl TouchingPolicy {
+    /// Offsets from a hit ship cell that can't hold any ship.
+    pub fn empty_around_hit(&self) -> &'static [(isize, isize)] {
+        match self {
+            TouchingPolicy::NoTouch => &DIAGONALS,
+            TouchingPolicy::CornersOnly | TouchingPolicy::Anything => &[],
+        }
+    }
+
+    /// Offsets from every cell of a ship that can't hold any other ship,
+    /// so they are empty around a sunk one.
+    pub fn empty_around_kill(&self) -> &'static [(isize, isize)] {
+        match self {
+            TouchingPolicy::NoTouch => &NEIGHBOURS,
+            TouchingPolicy::CornersOnly => &EDGES,
+            TouchingPolicy::Anything => &[],
+        }
+    }
+}
+
+impl Display for TouchingPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TouchingPolicy::NoTouch => write!(f, "no-touch"),
+            TouchingPolicy::CornersOnly => write!(f, "corners-only"),
+            TouchingPolicy::Anything => write!(f, "anything"),
+        }
+    }
+}
+
+impl FromStr for TouchingPolicy {
+    type Err = ParseTouchingPolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "no-touch" => Ok(TouchingPolicy::NoTouch),
+            "corners-only" => Ok(TouchingPolicy::CornersOnly),
+            "anything" => Ok(TouchingPolicy::Anything),
+            _ => Err(ParseTouchingPolicyError::InvalidInput),
+        }
+    }
+}
+
+pub enum ParseTouchingPolicyError {
+    InvalidInput,
+}
+
+impl Display for ParseTouchingPolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseTouchingPolicyError::InvalidInput => {
+                write!(f, "touching policy must be one of 'no-touch'/'corners-only'/'anything'")
+            }
+        }
+    }
+}
+
+/// How many shots a player fires every turn. With more than one, all
+/// shots of a turn are fired together and only then resolved.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Salvo {
+    /// One shot a turn, the classic game.
+    Off,
+    /// As many shots as the player has ships afloat.
+    ShipsAfloat,
+    /// The same number of shots every turn.
+    Fixed(usize),
+}
+
+impl Display for Salvo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Salvo::Off => write!(f, "off"),
+            Salvo::ShipsAfloat => write!(f, "ships"),
+            Salvo::Fixed(shots) => write!(f, "{shots}"),
+        }
+    }
+}
+
+impl FromStr for Salvo {
+    type Err = ParseSalvoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Salvo::Off),
+            "ships" => Ok(Salvo::ShipsAfloat),
+            _ => match s.parse() {
+                Ok(0) => Err(ParseSalvoError::NoShots),
+                Ok(shots) if shots > MAX_SALVO => Err(ParseSalvoError::TooManyShots),
+                Ok(shots) => Ok(Salvo::Fixed(shots)),
+                Err(_) => Err(ParseSalvoError::InvalidInput),
+            },
+        }
+    }
+}
+
+pub enum ParseSalvoError {
+    InvalidInput,
+    NoShots,
+    TooManyShots,
+}
+
+impl Display for ParseSalvoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseSalvoError::InvalidInput => write!(f, "salvo must be 'off', 'ships' or a number of shots"),
+            ParseSalvoError::NoShots => write!(f, "a salvo needs at least one shot"),
+            ParseSalvoError::TooManyShots => write!(f, "a salvo can have at most {MAX_SALVO} shots"),
+        }
+    }
+}
+
+/// Who shoots after a volley.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum TurnOrder {
+    /// The players take turns, whatever the shots did.
+    Alternate,
+    /// A player whose volley hit or sunk a ship shoots again, as in most
+    /// Russian rules.
+    HitShootsAgain,
+}
+
+impl Display for TurnOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TurnOrder::Alternate => write!(f, "alternate"),
+            TurnOrder::HitShootsAgain => write!(f, "hit-again"),
+        }
+    }
+}
+
+impl FromStr for TurnOrder {
+    type Err = ParseTurnOrderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alternate" => Ok(TurnOrder::Alternate),
+            "hit-again" => Ok(TurnOrder::HitShootsAgain),
+            _ => Err(ParseTurnOrderError::InvalidInput),
+        }
+    }
+}
+
+pub enum ParseTurnOrderError {
+    InvalidInput,
+}
+
+impl Display for ParseTurnOrderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseTurnOrderError::InvalidInput => write!(f, "turns must be 'alternate' or 'hit-again'"),
+        }
+    }
+}
+
+/// How many columns and rows a field has. Written as `10` for a square
+/// field and as `12x8` (width, then height) otherwise.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub struct FieldSize {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl FieldSize {
+    pub fn square(size: usize) -> FieldSize {
+        FieldSize { width: size, height: size }
+    }
+
+    pub fn cells(&self) -> usize {
+        self.width * self.height
+    }
+}
+
+impl Display for FieldSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.width == self.height {
+            write!(f, "{}", self.width)
+        } else {
+            write!(f, "{}x{}", self.width, self.height)
+        }
+    }
+}
+
+impl FromStr for FieldSize {
+    type Err = ParseFieldSizeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s.split_once(['x', 'X']).unwrap_or((s, s));
+        let width = width.trim().parse().map_err(|_| ParseFieldSizeError::InvalidInput)?;
+        let height = height.trim().parse().map_err(|_| ParseFieldSizeError::InvalidInput)?;
+
+        if width == 0 || height == 0 {
+            return Err(ParseFieldSizeError::Empty);
+        }
+        Ok(FieldSize { width, height })
+    }
+}
+
+pub enum ParseFieldSizeError {
+    InvalidInput,
+    Empty,
+}
+
+impl Display for ParseFieldSizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseFieldSizeError::InvalidInput => write!(f, "the size must look like '10' or '12x8'"),
+            ParseFieldSizeError::Empty => write!(f, "the field must have at least one row and column"),
+        }
+    }
+}
+
+/// Everything that decides how a match is played.
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Rules {
+    pub size: FieldSize,
+    /// Every ship each player gets.
+    pub fleet: Vec<Shape>,
+    pub touching: TouchingPolicy,
+    pub salvo: Salvo,
+    pub turns: TurnOrder,
+    /// Special attacks each player starts with, none in the classic game.
+    pub arsenal: Arsenal,
+    /// Cells of land, as `(x, y)`, on both fields alike. Everyone can see
+    /// them and no ship can be placed or shot there.
+    pub islands: Vec<(usize, usize)>,
+    /// How many mines every player hides on their field.
+    pub mines: usize,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            size: FieldSize::square(FIELD_SIZE),
+            fleet: FLEET.to_vec(),
+            touching: TouchingPolicy::NoTouch,
+            salvo: Salvo::Off,
+            turns: TurnOrder::Alternate,
+            arsenal: Arsenal::default(),
+            islands: Vec::new(),
+            mines: 0,
+        }
+    }
+}
@@ -0,0 +1,494 @@
+//! Games on hold, stored as plain text so they can be read and fixed by
+//! hand. Lines starting with `#` are ignored. A save looks like this:
+//!
+//! ```text
+//! version 1
+//! size 10
+//! fleet 4 3 3 2 2 2 1 1 1 1
+//! touching no-touch
+//! salvo off
+//! turns alternate
+//! arsenal 0,0,0
+//! islands E:5 E:6
+//! mines 2
+//! phase shooting 2
+//! player 1
+//! ships 10
+//! 4:A:1:h
+//! ...
+//! mined C:7 J:2
+//! field
+//! SSSS-o----
+//! ...
+//! checked
+//! --o--x*.--
+//! ...
+//! player 2
+//! ...
+//! shots 21
+//! 1 A:5
+//! 2 bomb C:3
+//! ...
+//! opponent
+//! budget samples 1000
+//! threads 8
+//! random 1234567
+//! prior none
+//! ```
+//!
+//! The size is `10` for a square field or `12x8` for 12 columns and 8 rows.
+//! The salvo is `off`, `ships` or a number of shots, and the turns are
+//! `alternate` or `hit-again`, and the arsenal is how many bombs, torpedoes
+//! and sonars every player starts with. The islands are cells of land on
+//! both fields, and the mines how many every player hides. Saves without
+//! them are played with one shot a turn, taking turns, without special
+//! attacks or terrain.
+//! The phase is `placement`, `shooting <side>` or `finished <winner>`.
+//! Every player lists their ships as `shape:y:x:rotation` and the cells
+//! of their mines, then their field (`-` empty, `S` ship, `o` enemy miss,
+//! `x` hit, `*` sunk, `^` island, `+` mine, `%` mine that went off) and
+//! their tracking grid (`-` unchecked, `o` miss, `x` hit, `*` sunk, `.`
+//! can't hold a ship, `^` island, `%` enemy mine), one row per letter. Shots are listed in order as
+//! `<side> y:x`, including the shots of a volley that isn't complete yet,
+//! and special attacks as `<side> bomb y:x`, `<side> torpedo y >` or
+//! `<side> sonar y:x`. Single player games end with the computer opponent: its
+//! budget (`samples <count>` or `ms <milliseconds>`), threads, random
+//! state and prior (`none`, or its weight followed by a grid of numbers).
+//!
+//! Loading places the ships and replays the shots, so the rules decide
+//! what the fields look like. The fields and the phase in the file must
+//! agree with the result, otherwise the save is rejected.
+
+use std::{fs, io, fmt::Write, path::Path, time::Duration};
+
+use super::{
+    game::{Game, Side, Phase, Command},
+    field::{FieldCell, CheckedCell},
+    rules::{Rules, FieldSize, Salvo, TurnOrder},
+    weapons::{Arsenal, Attack},
+    terrain,
+    game_constants::MAX_FIELD_SIZE,
+    shape,
+    ship::Ship,
+    shot::Shot,
+    ai::monte_carlo::{MonteCarloShooter, Budget},
+};
+use crate::utilities::random::Random;
+
+const SAVE_VERSION: u32 = 1;
+
+/// Where single player and hotseat games keep their latest state, so an
+/// interrupted game can be picked up again.
+pub const AUTOSAVE_PATH: &str = "autosave.txt";
+
+/// A game read back from a save, with the computer opponent if it was a
+/// single player game.
+pub struct SavedGame {
+    pub game: Game,
+    pub opponent: Option<MonteCarloShooter>,
+}
+
+/// Writes the save next to `path` first and then moves it over, so a
+/// crash halfway through never leaves a broken save behind.
+pub fn save(path: impl AsRef<Path>, game: &Game, opponent: Option<&MonteCarloShooter>) -> Result<(), SaveError> {
+    let path = path.as_ref();
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+
+    fs::write(&temporary, to_text(game, opponent)).map_err(SaveError::IOError)?;
+    fs::rename(&temporary, path).map_err(SaveError::IOError)
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<SavedGame, SaveError> {
+    let contents = fs::read_to_string(path).map_err(SaveError::IOError)?;
+    from_text(&contents)
+}
+
+pub fn to_text(game: &Game, opponent: Option<&MonteCarloShooter>) -> String {
+    let mut text = String::new();
+    let rules = game.rules();
+
+    // Writing to a String can't fail.
+    let _ = writeln!(text, "# battleships save");
+    let _ = writeln!(text, "version {SAVE_VERSION}");
+    let _ = writeln!(text, "size {}", rules.size);
+    let _ = writeln!(text, "fleet {}", shape::format_fleet(&rules.fleet));
+    let _ = writeln!(text, "touching {}", rules.touching);
+    let _ = writeln!(text, "salvo {}", rules.salvo);
+    let _ = writeln!(text, "turns {}", rules.turns);
+    let _ = writeln!(text, "arsenal {}", rules.arsenal);
+    let _ = writeln!(text, "{}", cells_line("islands", rules.islands.iter().copied()));
+    let _ = writeln!(text, "mines {}", rules.mines);
+    let _ = writeln!(text, "phase {}", phase_to_text(game.phase()));
+
+    for side in [Side::First, Side::Second] {
+        let player = game.player(side);
+        let _ = writeln!(text, "player {}", side.index() + 1);
+        let _ = writeln!(text, "ships {}", player.ships().len());
+        for ship in player.ships() {
+            let _ = writeln!(text, "{ship}");
+        }
+        let _ = writeln!(text, "{}", cells_line("mined", player.mines().iter().map(|mine| (mine.x, mine.y))));
+        let _ = writeln!(text, "field");
+        text.push_str(&grid(rules.size, |x, y| field_cell_to_char(player.player_field().at(x, y))));
+        let _ = writeln!(text, "checked");
+        text.push_str(&grid(rules.size, |x, y| checked_cell_to_char(player.checked_field().at(x, y))));
+    }
+
+    let _ = writeln!(text, "shots {}", game.history().len() + game.pending_shots().len());
+    for (side, attack) in game.history() {
+        let _ = writeln!(text, "{} {attack}", side.index() + 1);
+    }
+    if let Phase::Shooting(side) = game.phase() {
+        for shot in game.pending_shots() {
+            let _ = writeln!(text, "{} {shot}", side.index() + 1);
+        }
+    }
+
+    if let Some(opponent) = opponent {
+        let _ = writeln!(text, "opponent");
+        match opponent.budget() {
+            Budget::Samples(samples) => { let _ = writeln!(text, "budget samples {samples}"); }
+            Budget::Time(time) => { let _ = writeln!(text, "budget ms {}", time.as_millis()); }
+        }
+        let _ = writeln!(text, "threads {}", opponent.threads());
+        let _ = writeln!(text, "random {}", opponent.random().state());
+        match opponent.prior() {
+            None => { let _ = writeln!(text, "prior none"); }
+            Some((prior, weight)) => {
+                let _ = writeln!(text, "prior {weight}");
+                for row in prior.chunks(rules.size.width) {
+                    let row: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+                    let _ = writeln!(text, "{}", row.join(" "));
+                }
+            }
+        }
+    }
+
+    text
+}
+
+pub fn from_text(contents: &str) -> Result<SavedGame, SaveError> {
+    let mut lines = contents.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#') && !line.is_empty())
+        .peekable();
+
+    let version: u32 = read_value(&mut lines, "version")?;
+    if version != SAVE_VERSION {
+        return Err(SaveError::UnknownVersion(version));
+    }
+    let size: FieldSize = read_value(&mut lines, "size")?;
+    if size.width > MAX_FIELD_SIZE || size.height > MAX_FIELD_SIZE {
+        return Err(SaveError::Corrupt(format!("unsupported field size {size}")));
+    }
+    let fleet = read_line(&mut lines, "fleet")?;
+    let fleet = shape::parse_fleet(fleet).map_err(|error| SaveError::Corrupt(format!("bad fleet: {error}")))?;
+    let touching = read_value(&mut lines, "touching")?;
+    let salvo = match lines.peek() {
+        Some(line) if line.starts_with("salvo") => read_value(&mut lines, "salvo")?,
+        _ => Salvo::Off,
+    };
+    let turns = match lines.peek() {
+        Some(line) if line.starts_with("turns") => read_value(&mut lines, "turns")?,
+        _ => TurnOrder::Alternate,
+    };
+    let arsenal = match lines.peek() {
+        Some(line) if line.starts_with("arsenal") => read_value(&mut lines, "arsenal")?,
+        _ => Arsenal::default(),
+    };
+    let islands = match lines.peek() {
+        Some(line) if line.starts_with("islands") => {
+            let islands = read_line(&mut lines, "islands")?;
+            terrain::parse_cells(islands, size).ok_or(SaveError::Corrupt(format!("bad islands '{islands}'")))?
+        }
+        _ => Vec::new(),
+    };
+    let mines = match lines.peek() {
+        Some(line) if line.starts_with("mines") => read_value(&mut lines, "mines")?,
+        _ => 0,
+    };
+    let phase = read_line(&mut lines, "phase")?.to_string();
+
+    let mut game = Game::new(Rules { size, fleet, touching, salvo, turns, arsenal, islands, mines });
+    let mut fields = Vec::new();
+
+    for side in [Side::First, Side::Second] {
+        let number: usize = read_value(&mut lines, "player")?;
+        if number != side.index() + 1 {
+            return Err(SaveError::Corrupt(format!("expected player {}, got player {number}", side.index() + 1)));
+        }
+
+        let ships: usize = read_value(&mut lines, "ships")?;
+        for _ in 0..ships {
+            let line = next_line(&mut lines)?;
+            let ship: Ship = line.parse().map_err(|error| SaveError::Corrupt(format!("bad ship '{line}': {error}")))?;
+            game.handle(Command::PlaceShip(side, ship))
+                .map_err(|error| SaveError::Inconsistent(format!("{side} can't place {ship}: {error}")))?;
+        }
+        let mines = match lines.peek() {
+            Some(line) if line.starts_with("mined") => {
+                let mines = read_line(&mut lines, "mined")?;
+                terrain::parse_cells(mines, size).ok_or(SaveError::Corrupt(format!("bad mines '{mines}'")))?
+            }
+            _ => Vec::new(),
+        };
+        for (x, y) in mines {
+            let mine = Shot { x, y };
+            game.handle(Command::PlaceMine(side, mine))
+                .map_err(|error| SaveError::Inconsistent(format!("{side} can't hide a mine at {mine}: {error}")))?;
+        }
+        if game.ships_to_place(side).is_empty() && game.mines_to_place(side) == 0 {
+            game.handle(Command::ConfirmFleet(side)).expect("the fleet is complete");
+        }
+
+        read_line(&mut lines, "field")?;
+        let field = read_grid(&mut lines, size)?;
+        read_line(&mut lines, "checked")?;
+        let checked = read_grid(&mut lines, size)?;
+        fields.push((side, field, checked));
+    }
+
+    let shots: usize = read_value(&mut lines, "shots")?;
+    for _ in 0..shots {
+        let line = next_line(&mut lines)?;
+        let (side, attack) = parse_shot_line(line)?;
+        game.handle(Command::attack(side, attack))
+            .map_err(|error| SaveError::Inconsistent(format!("{side} can't fire {attack}: {error}")))?;
+    }
+
+    if phase != phase_to_text(game.phase()) {
+        return Err(SaveError::Inconsistent(format!(
+            "the phase is '{phase}', but the ships and shots say '{}'", phase_to_text(game.phase())
+        )));
+    }
+    for (side, field, checked) in fields {
+        let player = game.player(side);
+        if field != grid(size, |x, y| field_cell_to_char(player.player_field().at(x, y))) {
+            return Err(SaveError::Inconsistent(format!("{side}'s field doesn't match the ships and shots")));
+        }
+        if checked != grid(size, |x, y| checked_cell_to_char(player.checked_field().at(x, y))) {
+            return Err(SaveError::Inconsistent(format!("{side}'s tracking grid doesn't match the shots")));
+        }
+    }
+
+    let opponent = match lines.next() {
+        None => None,
+        Some("opponent") => Some(read_opponent(&mut lines, game.rules())?),
+        Some(line) => return Err(SaveError::Corrupt(format!("unexpected line '{line}'"))),
+    };
+    if let Some(line) = lines.next() {
+        return Err(SaveError::Corrupt(format!("unexpected line '{line}'")));
+    }
+
+    Ok(SavedGame { game, opponent })
+}
+
+fn read_opponent<'a>(lines: &mut impl Iterator<Item = &'a str>, rules: &Rules) -> Result<MonteCarloShooter, SaveError> {
+    let size = rules.size;
+    let budget = read_line(lines, "budget")?;
+    let budget = match budget.split_once(' ') {
+        Some(("samples", samples)) => samples.trim().parse().ok().map(Budget::Samples),
+        Some(("ms", milliseconds)) => milliseconds.trim().parse().ok().map(|ms| Budget::Time(Duration::from_millis(ms))),
+        _ => None,
+    }.ok_or(SaveError::Corrupt(format!("bad budget '{budget}'")))?;
+    let threads: usize = read_value(lines, "threads")?;
+    let random: u64 = read_value(lines, "random")?;
+
+    let mut opponent = MonteCarloShooter::new(budget, threads);
+    opponent.set_random(Random::from_seed(random));
+    opponent.set_fleet(&rules.fleet);
+
+    let prior = read_line(lines, "prior")?;
+    if prior != "none" {
+        let weight: f64 = prior.parse().map_err(|_| SaveError::Corrupt(format!("bad prior weight '{prior}'")))?;
+        let mut values = Vec::with_capacity(size.cells());
+        for _ in 0..size.height {
+            let line = next_line(lines)?;
+            let row: Result<Vec<f64>, _> = line.split_whitespace().map(str::parse).collect();
+            match row {
+                Ok(row) if row.len() == size.width => values.extend(row),
+                _ => return Err(SaveError::Corrupt(format!("bad prior row '{line}'"))),
+            }
+        }
+        opponent.set_prior(values, weight);
+    }
+
+    Ok(opponent)
+}
+
+fn phase_to_text(phase: Phase) -> String {
+    match phase {
+        Phase::Placement => "placement".to_string(),
+        Phase::Shooting(side) => format!("shooting {}", side.index() + 1),
+        Phase::Finished(winner) => format!("finished {}", winner.index() + 1),
+    }
+}
+
+fn parse_side(text: &str) -> Option<Side> {
+    match text {
+        "1" => Some(Side::First),
+        "2" => Some(Side::Second),
+        _ => None,
+    }
+}
+
+fn parse_shot_line(line: &str) -> Result<(Side, Attack), SaveError> {
+    let corrupt = || SaveError::Corrupt(format!("bad shot '{line}'"));
+
+    let (side, attack) = line.split_once(' ').ok_or_else(corrupt)?;
+    let side = parse_side(side).ok_or_else(corrupt)?;
+    let attack = attack.trim().parse().map_err(|_| corrupt())?;
+    Ok((side, attack))
+}
+
+/// A line of `name` followed by `cells`, without a trailing space if
+/// there are none.
+fn cells_line(name: &str, cells: impl IntoIterator<Item = (usize, usize)>) -> String {
+    let cells = terrain::format_cells(cells);
+    if cells.is_empty() {
+        name.to_string()
+    } else {
+        format!("{name} {cells}")
+    }
+}
+
+/// The cells of a field, one row of characters per letter.
+fn grid(size: FieldSize, cell: impl Fn(usize, usize) -> char) -> String {
+    let mut text = String::with_capacity((size.width + 1) * size.height);
+    for y in 0..size.height {
+        text.extend((0..size.width).map(|x| cell(x, y)));
+        text.push('\n');
+    }
+    text
+}
+
+fn field_cell_to_char(cell: FieldCell) -> char {
+    match cell {
+        FieldCell::Empty => '-',
+        FieldCell::Ship => 'S',
+        FieldCell::EnemyMiss => 'o',
+        FieldCell::Hit => 'x',
+        FieldCell::Dead => '*',
+        FieldCell::Island => '^',
+        FieldCell::Mine => '+',
+        FieldCell::Crater => '%',
+    }
+}
+
+fn checked_cell_to_char(cell: CheckedCell) -> char {
+    match cell {
+        CheckedCell::Unchecked => '-',
+        CheckedCell::Miss => 'o',
+        CheckedCell::Hit => 'x',
+        CheckedCell::Kill => '*',
+        CheckedCell::KnownEmpty => '.',
+        CheckedCell::Island => '^',
+        CheckedCell::Mine => '%',
+    }
+}
+
+fn next_line<'a>(lines: &mut impl Iterator<Item = &'a str>) -> Result<&'a str, SaveError> {
+    lines.next().ok_or(SaveError::Corrupt("the save ends too early".to_string()))
+}
+
+/// The rest of a line that starts with `name`.
+fn read_line<'a>(lines: &mut impl Iterator<Item = &'a str>, name: &str) -> Result<&'a str, SaveError> {
+    let line = next_line(lines)?;
+    match line.strip_prefix(name) {
+        Some(rest) if rest.is_empty() || rest.starts_with(' ') => Ok(rest.trim()),
+        _ => Err(SaveError::Corrupt(format!("expected '{name}', got '{line}'"))),
+    }
+}
+
+fn read_value<'a, T: std::str::FromStr>(lines: &mut impl Iterator<Item = &'a str>, name: &str) -> Result<T, SaveError> {
+    let value = read_line(lines, name)?;
+    value.parse().map_err(|_| SaveError::Corrupt(format!("bad value '{value}' for '{name}'")))
+}
+
+/// Reads the rows of a grid back in the form `grid` writes them.
+fn read_grid<'a>(lines: &mut impl Iterator<Item = &'a str>, size: FieldSize) -> Result<String, SaveError> {
+    let mut text = String::with_capacity((size.width + 1) * size.height);
+    for _ in 0..size.height {
+        let line = next_line(lines)?;
+        if line.chars().count() != size.width {
+            return Err(SaveError::Corrupt(format!("bad grid row '{line}'")));
+        }
+        text.push_str(line);
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    IOError(io::Error),
+    UnknownVersion(u32),
+    /// The file can't be read as a save at all.
+    Corrupt(String),
+    /// The file reads fine, but describes a game that can't happen.
+    Inconsistent(String),
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::IOError(inner) => write!(f, "can't access the save: {inner}"),
+            SaveError::UnknownVersion(version) => write!(f, "unknown save version {version}"),
+            SaveError::Corrupt(reason) => write!(f, "the save is corrupt: {reason}"),
+            SaveError::Inconsistent(reason) => write!(f, "the save doesn't add up: {reason}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battleships::{shape::Shape, ship::Rotation, weapons::Special};
+
+    /// A game in the middle of shooting, with every rule a save has to keep.
+    fn played_game() -> Game {
+        let rules = Rules {
+            size: FieldSize { width: 6, height: 5 },
+            fleet: vec![Shape::Line(3), Shape::Line(1)],
+            turns: TurnOrder::HitShootsAgain,
+            arsenal: Arsenal { bombs: 1, torpedoes: 0, sonars: 0 },
+            islands: vec![(5, 0)],
+            mines: 1,
+            ..Rules::default()
+        };
+        let mut game = Game::new(rules);
+        let first = [Ship::line(3, 0, 0, Rotation::Horizontal), Ship::line(1, 0, 2, Rotation::Horizontal)];
+        let second = [Ship::line(3, 0, 4, Rotation::Horizontal), Ship::line(1, 4, 2, Rotation::Horizontal)];
+        game.place_fleet_and_mines(Side::First, &first, &[Shot { x: 4, y: 4 }]).unwrap();
+        game.place_fleet_and_mines(Side::Second, &second, &[Shot { x: 2, y: 2 }]).unwrap();
+
+        for (side, attack) in [
+            (Side::First, Attack::Shot(Shot { x: 0, y: 4 })),
+            (Side::First, Attack::Shot(Shot { x: 5, y: 3 })),
+            (Side::Second, Attack::Special(Special::Bomb(Shot { x: 4, y: 1 }))),
+            (Side::First, Attack::Shot(Shot { x: 2, y: 2 })),
+        ] {
+            game.handle(Command::attack(side, attack)).unwrap();
+        }
+        game
+    }
+
+    #[test]
+    fn round_trips_a_game() {
+        let game = played_game();
+        let text = to_text(&game, None);
+        let loaded = from_text(&text).unwrap();
+
+        assert!(loaded.opponent.is_none());
+        assert_eq!(loaded.game.phase(), game.phase());
+        assert_eq!(loaded.game.history().len(), 4);
+        assert_eq!(to_text(&loaded.game, None), text);
+    }
+
+    #[test]
+    fn refuses_fields_larger_than_any_game() {
+        let text = to_text(&played_game(), None).replace("size 6x5", "size 18446744073709551615x5");
+        assert!(matches!(from_text(&text), Err(SaveError::Corrupt(_))));
+    }
+}
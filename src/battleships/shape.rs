@@ -0,0 +1,209 @@
+//! What ships look like. Besides the classic straight ones there are the
+//! tetrominoes of the "Tetris fleet" variant. Every shape is a list of
+//! cell offsets, which can be turned four ways and mirrored.
+
+use std::{fmt::Display, str::FromStr};
+
+use super::{
+    game_constants::{MAX_FIELD_SIZE, MAX_FLEET, TETRIS_FLEET},
+    ship::Rotation,
+};
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Shape {
+    /// A straight line of this many cells.
+    Line(usize),
+    L,
+    T,
+    /// The 2×2 block, written `O` like the tetromino.
+    Square,
+    Z,
+}
+
+const L_CELLS: [(usize, usize); 4] = [(0, 0), (0, 1), (0, 2), (1, 2)];
+const T_CELLS: [(usize, usize); 4] = [(0, 0), (1, 0), (2, 0), (1, 1)];
+const SQUARE_CELLS: [(usize, usize); 4] = [(0, 0), (1, 0), (0, 1), (1, 1)];
+const Z_CELLS: [(usize, usize); 4] = [(0, 0), (1, 0), (1, 1), (2, 1)];
+
+impl Shape {
+    /// How many cells the shape has.
+    pub fn size(self) -> usize {
+        match self {
+            Shape::Line(length) => length,
+            _ => 4,
+        }
+    }
+
+    pub fn is_straight(self) -> bool {
+        matches!(self, Shape::Line(_))
+    }
+
+    /// The longer side of the box around the shape.
+    pub fn extent(self) -> usize {
+        match self {
+            Shape::Line(length) => length,
+            Shape::Square => 2,
+            _ => 3,
+        }
+    }
+
+    /// The `index`th cell of the shape lying flat, as `(x, y)` from the
+    /// top left corner of the shape. Lines lie horizontally.
+    fn offset(self, index: usize) -> (usize, usize) {
+        match self {
+            Shape::Line(_) => (index, 0),
+            Shape::L => L_CELLS[index],
+            Shape::T => T_CELLS[index],
+            Shape::Square => SQUARE_CELLS[index],
+            Shape::Z => Z_CELLS[index],
+        }
+    }
+
+    /// The `index`th cell once the shape is flipped left to right, if
+    /// `mirrored`, and then turned clockwise by `rotation`. The result can
+    /// be anywhere around the origin, `cells` moves it back.
+    fn turned_offset(self, index: usize, rotation: Rotation, mirrored: bool) -> (isize, isize) {
+        let (x, y) = self.offset(index);
+        let (x, y) = (if mirrored { -(x as isize) } else { x as isize }, y as isize);
+        match rotation {
+            Rotation::Horizontal => (x, y),
+            Rotation::Vertical => (-y, x),
+            Rotation::HorizontalReversed => (-x, -y),
+            Rotation::VerticalReversed => (y, -x),
+        }
+    }
+
+    /// The cells of the shape lying as `rotation` and `mirrored` say, as
+    /// `(x, y)` from the top left corner of the box around them.
+    pub fn cells(self, rotation: Rotation, mirrored: bool) -> impl Iterator<Item = (usize, usize)> {
+        let turned = move |index| self.turned_offset(index, rotation, mirrored);
+        let (left, top) = (0..self.size())
+            .map(turned)
+            .fold((0, 0), |(left, top), (x, y)| (x.min(left), y.min(top)));
+
+        (0..self.size()).map(move |index| {
+            let (x, y) = turned(index);
+            ((x - left) as usize, (y - top) as usize)
+        })
+    }
+
+    /// Every way the shape can lie that covers different cells, as the
+    /// rotation and whether it's mirrored.
+    pub fn orientations(self) -> Vec<(Rotation, bool)> {
+        let mut seen: Vec<Vec<(usize, usize)>> = Vec::new();
+        let mut orientations = Vec::new();
+
+        for mirrored in [false, true] {
+            for rotation in Rotation::ALL {
+                let mut cells: Vec<(usize, usize)> = self.cells(rotation, mirrored).collect();
+                cells.sort_unstable();
+                if !seen.contains(&cells) {
+                    seen.push(cells);
+                    orientations.push((rotation, mirrored));
+                }
+            }
+        }
+        orientations
+    }
+
+    /// The way from `orientations` that covers the same cells as lying
+    /// `rotation`, mirrored or not.
+    pub fn orientation_of(self, rotation: Rotation, mirrored: bool) -> (Rotation, bool) {
+        let sorted_cells = |rotation, mirrored| {
+            let mut cells: Vec<(usize, usize)> = self.cells(rotation, mirrored).collect();
+            cells.sort_unstable();
+            cells
+        };
+        let cells = sorted_cells(rotation, mirrored);
+        self.orientations().into_iter()
+            .find(|&(other_rotation, other_mirrored)| sorted_cells(other_rotation, other_mirrored) == cells)
+            .unwrap_or((rotation, mirrored))
+    }
+
+    /// The shape in a sentence, like "a ship of length 3" or "an L-shaped
+    /// ship".
+    pub fn describe(self) -> String {
+        match self {
+            Shape::Line(length) => format!("a ship of length {length}"),
+            Shape::L | Shape::Square => format!("an {self}-shaped ship"),
+            _ => format!("a {self}-shaped ship"),
+        }
+    }
+}
+
+impl Display for Shape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Shape::Line(length) => write!(f, "{length}"),
+            Shape::L => write!(f, "L"),
+            Shape::T => write!(f, "T"),
+            Shape::Square => write!(f, "O"),
+            Shape::Z => write!(f, "Z"),
+        }
+    }
+}
+
+impl FromStr for Shape {
+    type Err = ParseShapeError;
+
+    /// Reads a length for a straight ship, or the letter of a tetromino.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "L" | "l" => Ok(Shape::L),
+            "T" | "t" => Ok(Shape::T),
+            "O" | "o" => Ok(Shape::Square),
+            "Z" | "z" => Ok(Shape::Z),
+            length => match length.parse() {
+                Ok(length) if length > MAX_FIELD_SIZE => Err(ParseShapeError::TooLong(length)),
+                Ok(length) => Ok(Shape::Line(length)),
+                Err(_) => Err(ParseShapeError::Unknown(length.to_string())),
+            },
+        }
+    }
+}
+
+/// Reads a whole fleet, like `4 3 3 2` or `4 L T O Z`, or `tetris` for
+/// the Tetris fleet.
+pub fn parse_fleet(text: &str) -> Result<Vec<Shape>, ParseShapeError> {
+    if text.trim() == "tetris" {
+        return Ok(TETRIS_FLEET.to_vec());
+    }
+    let fleet: Vec<Shape> = text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|shape| !shape.is_empty())
+        .map(str::parse)
+        .collect::<Result<_, _>>()?;
+    if fleet.is_empty() {
+        return Err(ParseShapeError::NoShips);
+    }
+    if fleet.len() > MAX_FLEET {
+        return Err(ParseShapeError::TooManyShips);
+    }
+    Ok(fleet)
+}
+
+/// A fleet written the way `parse_fleet` reads it.
+pub fn format_fleet(fleet: &[Shape]) -> String {
+    let shapes: Vec<String> = fleet.iter().map(|shape| shape.to_string()).collect();
+    shapes.join(" ")
+}
+
+#[derive(Debug)]
+pub enum ParseShapeError {
+    Unknown(String),
+    TooLong(usize),
+    NoShips,
+    TooManyShips,
+}
+
+impl Display for ParseShapeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseShapeError::Unknown(shape) => write!(f, "'{shape}' isn't a ship, give a length or one of L, T, O, Z"),
+            ParseShapeError::TooLong(length) => write!(f, "a ship of length {length} is longer than any field"),
+            ParseShapeError::NoShips => write!(f, "the fleet needs at least one ship"),
+            ParseShapeError::TooManyShips => write!(f, "a fleet can have at most {MAX_FLEET} ships"),
+        }
+    }
+}
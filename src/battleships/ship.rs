@@ -1,12 +1,44 @@
-use std::{str::FromStr, num::ParseIntError, fmt::Display, char::ParseCharError};
+use std::{str::FromStr, fmt::Display};
 use crate::utilities::conversions;
-use super::game_constants::{FIRST_LETTER, LAST_LETTER};
+use super::coordinate::{self, CoordinateError};
+use super::shape::{Shape, ParseShapeError};
+use super::shot::{Shot, ParseShotError};
 
+/// How far a ship is turned clockwise from lying flat. Straight ships
+/// only need the first two.
 #[derive(Debug)]
 #[derive(Clone, Copy)]
+#[derive(PartialEq)]
 pub enum Rotation {
     Horizontal,
-    Vertical
+    Vertical,
+    /// Turned half way round.
+    HorizontalReversed,
+    /// Turned three quarters of the way round.
+    VerticalReversed,
+}
+
+impl Rotation {
+    pub const ALL: [Rotation; 4] = [
+        Rotation::Horizontal,
+        Rotation::Vertical,
+        Rotation::HorizontalReversed,
+        Rotation::VerticalReversed,
+    ];
+
+    /// Turned another quarter clockwise.
+    pub fn turned(self) -> Rotation {
+        match self {
+            Rotation::Horizontal => Rotation::Vertical,
+            Rotation::Vertical => Rotation::HorizontalReversed,
+            Rotation::HorizontalReversed => Rotation::VerticalReversed,
+            Rotation::VerticalReversed => Rotation::Horizontal,
+        }
+    }
+
+    pub fn is_horizontal(self) -> bool {
+        matches!(self, Rotation::Horizontal | Rotation::HorizontalReversed)
+    }
 }
 
 impl FromStr for Rotation {
@@ -16,58 +48,120 @@ impl FromStr for Rotation {
         match s {
             "h" => Ok(Rotation::Horizontal),
             "v" => Ok(Rotation::Vertical),
+            "hr" => Ok(Rotation::HorizontalReversed),
+            "vr" => Ok(Rotation::VerticalReversed),
             _ => Err(ParseRotationError::InvalidInput),
         }
     }
 }
 
+impl Display for Rotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rotation::Horizontal => write!(f, "h"),
+            Rotation::Vertical => write!(f, "v"),
+            Rotation::HorizontalReversed => write!(f, "hr"),
+            Rotation::VerticalReversed => write!(f, "vr"),
+        }
+    }
+}
+
+/// A placed ship. `(x, y)` is the top left corner of the box around it,
+/// which is its first cell for straight ships.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
 pub struct Ship {
-    pub length: usize,
+    pub shape: Shape,
     pub x: usize,
     pub y: usize,
     pub rotation: Rotation,
+    /// Flipped left to right before it's turned.
+    pub mirrored: bool,
 }
 
-impl FromStr for Ship {
-    type Err = ParseShipError;
+impl Ship {
+    /// A straight ship of `length` starting at `(x, y)`.
+    pub fn line(length: usize, x: usize, y: usize, rotation: Rotation) -> Ship {
+        Ship { shape: Shape::Line(length), x, y, rotation, mirrored: false }
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut ship_iter = s.split(':');
+    /// How many cells the ship has.
+    pub fn size(&self) -> usize {
+        self.shape.size()
+    }
 
-        let length = match ship_iter.next() {
-            Some(value) => value,
-            None => return Err(ParseShipError::MissingInfo),
-        };
-        let length = match length.parse::<usize>() {
-            Ok(value) => value,
-            Err(error) => return Err(ParseShipError::ParseIntError(error)),
+    /// Every `(x, y)` the ship occupies, starting from its origin for
+    /// straight ships. Cells too far out to count stay at `usize::MAX`,
+    /// which is off every field.
+    pub fn cells(&self) -> impl Iterator<Item = (usize, usize)> {
+        let Ship { x, y, .. } = *self;
+        self.shape.cells(self.rotation, self.mirrored).map(move |(dx, dy)| (x.saturating_add(dx), y.saturating_add(dy)))
+    }
+
+    /// The ship turned a quarter clockwise in place. Straight ships just
+    /// switch between horizontal and vertical.
+    pub fn turned(self) -> Ship {
+        let rotation = match (self.shape, self.rotation) {
+            (Shape::Line(_), Rotation::Horizontal | Rotation::HorizontalReversed) => Rotation::Vertical,
+            (Shape::Line(_), _) => Rotation::Horizontal,
+            (_, rotation) => rotation.turned(),
         };
+        Ship { rotation, ..self }
+    }
+
+    /// The ship flipped left to right in place, lying the way
+    /// `normalised` says.
+    pub fn mirrored(self) -> Ship {
+        Ship { mirrored: !self.mirrored, ..self }.normalised()
+    }
+
+    /// The ship lying the way `Shape::orientations` names the cells it
+    /// covers, so that ships on the same cells are equal.
+    pub fn normalised(self) -> Ship {
+        let (rotation, mirrored) = self.shape.orientation_of(self.rotation, self.mirrored);
+        Ship { rotation, mirrored, ..self }
+    }
 
-        let y = match ship_iter.next() {
+    /// The ship with its ends at `start` and `end`, which may be the same
+    /// cell. `None` when they aren't on one row or column.
+    pub fn between(start: Shot, end: Shot) -> Option<Ship> {
+        let (x, y) = (start.x.min(end.x), start.y.min(end.y));
+        if start.y == end.y {
+            Some(Ship::line(start.x.abs_diff(end.x) + 1, x, y, Rotation::Horizontal))
+        } else if start.x == end.x {
+            Some(Ship::line(start.y.abs_diff(end.y) + 1, x, y, Rotation::Vertical))
+        } else {
+            None
+        }
+    }
+
+    /// `shape:y:x:rotation`, followed by `:m` if mirrored. Shapes that
+    /// look the same whichever way they lie can leave the rotation out.
+    fn from_colons(s: &str) -> Result<Ship, ParseShipError> {
+        let mut ship_iter = s.split(':');
+
+        let shape = match ship_iter.next() {
             Some(value) => value,
             None => return Err(ParseShipError::MissingInfo),
         };
-        let y = match y.parse::<char>() {
+        let shape = match shape.parse::<Shape>() {
             Ok(value) => value,
-            Err(error) => return Err(ParseShipError::ParseCharError(error)),
-        };
-        let y = match conversions::coordinate_to_usize(y) {
-            Some(value) => value,
-            None => return Err(ParseShipError::ConversionToCharError),
+            Err(error) => return Err(ParseShipError::BadShape(error)),
         };
 
-        let x = match ship_iter.next() {
-            Some(value) => value,
-            None => return Err(ParseShipError::MissingInfo),
+        let (y, x) = match (ship_iter.next(), ship_iter.next()) {
+            (Some(y), Some(x)) => (y, x),
+            _ => return Err(ParseShipError::MissingInfo),
         };
-        let x = match x.parse::<usize>() {
+        let Shot { x, y } = match coordinate::parse(&format!("{y}:{x}")) {
             Ok(value) => value,
-            Err(error) => return Err(ParseShipError::ParseIntError(error)),
+            Err(error) => return Err(ParseShipError::BadCoordinate(error)),
         };
-        let x = x - 1;
 
         let rotation = match ship_iter.next() {
             Some(value) => value,
+            None if shape.orientations().len() == 1 => "h",
             None => return Err(ParseShipError::MissingInfo),
         };
         let rotation = match rotation.parse::<Rotation>() {
@@ -75,28 +169,196 @@ impl FromStr for Ship {
             Err(error) => return Err(ParseShipError::ParseRotationError(error)),
         };
 
+        let mirrored = match ship_iter.next() {
+            None => false,
+            Some("m") => true,
+            Some(_) => return Err(ParseShipError::TrailingInput),
+        };
+        if ship_iter.next().is_some() {
+            return Err(ParseShipError::TrailingInput);
+        }
+
         Ok(Ship {
-            length, x, y, rotation
-        })
+            shape, x, y, rotation, mirrored
+        }.normalised())
+    }
+
+    /// `shape cell [rotation] [m]` for the shaped ships, like `L B3 v m`,
+    /// with the top left corner of the box around the ship at the cell.
+    fn from_words(shape: Shape, words: &[&str]) -> Result<Ship, ParseShipError> {
+        let corner = words.first().ok_or(ParseShipError::MissingInfo)?;
+        let Shot { x, y } = coordinate::parse(corner).map_err(ParseShipError::BadCoordinate)?;
+
+        let mut ship = Ship { shape, x, y, rotation: Rotation::Horizontal, mirrored: false };
+        match words[1..] {
+            [] => {}
+            ["m"] => ship.mirrored = true,
+            [rotation] | [rotation, "m"] => {
+                ship.rotation = rotation.parse().map_err(ParseShipError::ParseRotationError)?;
+                ship.mirrored = words.len() == 3;
+            }
+            _ => return Err(ParseShipError::TrailingInput),
+        }
+        Ok(ship)
+    }
+}
+
+impl FromStr for Ship {
+    type Err = ParseShipError;
+
+    /// Reads a ship from its two ends, like `B3-B6` or `B3 B6`, from a
+    /// single cell like `E7` for one-cell ships, from its shape and
+    /// corner like `T C5 v`, or as `shape:y:x:rotation`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        // `5:A` and `L:5` are cells, `5:A:1:h` and `L:E:5:v` ships.
+        let parts: Vec<&str> = s.split(':').collect();
+        if parts.len() >= 3 && parts[0].parse::<Shape>().is_ok() {
+            return Ship::from_colons(s);
+        }
+
+        // A single cell can have a space inside it too, like `E 7`.
+        if !s.contains('-') {
+            if let Ok(cell) = coordinate::parse(s) {
+                return Ok(Ship::line(1, cell.x, cell.y, Rotation::Horizontal));
+            }
+        }
+
+        let words: Vec<&str> = s.split_whitespace().collect();
+        match words.first().map(|word| word.parse::<Shape>()) {
+            Some(Ok(Shape::Line(_))) | Some(Err(_)) | None => {}
+            Some(Ok(shape)) => return Ship::from_words(shape, &words[1..]),
+        }
+
+        let mut ends = s.split(|c: char| c == '-' || c.is_whitespace()).filter(|end| !end.is_empty());
+        let start = ends.next().ok_or(ParseShipError::MissingInfo)?;
+        let start = coordinate::parse(start).map_err(ParseShipError::BadCoordinate)?;
+        let end = match ends.next() {
+            Some(end) => coordinate::parse(end).map_err(ParseShipError::BadCoordinate)?,
+            None => start,
+        };
+        if ends.next().is_some() {
+            return Err(ParseShipError::TooManyCells);
+        }
+
+        Ship::between(start, end).ok_or(ParseShipError::NotStraight)
+    }
+}
+
+impl Display for Ship {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let y = conversions::usize_to_label(self.y);
+        write!(f, "{}:{y}:{}:{}", self.shape, self.x + 1, self.rotation)?;
+        if self.mirrored {
+            write!(f, ":m")?;
+        }
+        Ok(())
+    }
+}
+
+/// Everything that can be typed at the ship placement prompt. Ships are
+/// numbered from 1 in the order they were placed.
+pub enum PlacementCommand {
+    Place(Ship),
+    /// Show the ships placed so far.
+    List,
+    Remove(usize),
+    /// Move a ship so that it starts at this cell.
+    Move(usize, Shot),
+    /// Turn a ship a quarter clockwise in place.
+    Rotate(usize),
+    /// Flip a ship left to right in place.
+    Mirror(usize),
+    /// Hide a mine in this cell, or lift the one that's there.
+    Mine(Shot),
+    /// Remove every ship.
+    Clear,
+    /// Lock the fleet in.
+    Confirm,
+    /// Take the last change back.
+    Undo,
+    /// Make the change taken back again.
+    Redo,
+}
+
+impl FromStr for PlacementCommand {
+    type Err = ParsePlacementCommandError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let number = |index: usize| -> Result<usize, ParsePlacementCommandError> {
+            match words.get(index).map(|word| word.parse::<usize>()) {
+                Some(Ok(number)) if number > 0 => Ok(number),
+                _ => Err(ParsePlacementCommandError::BadShipNumber),
+            }
+        };
+
+        match words.first().copied() {
+            Some("list") => Ok(PlacementCommand::List),
+            Some("clear") => Ok(PlacementCommand::Clear),
+            Some("done" | "confirm") => Ok(PlacementCommand::Confirm),
+            Some("undo") => Ok(PlacementCommand::Undo),
+            Some("redo") => Ok(PlacementCommand::Redo),
+            Some("remove") => number(1).map(PlacementCommand::Remove),
+            Some("rotate") => number(1).map(PlacementCommand::Rotate),
+            Some("mirror") => number(1).map(PlacementCommand::Mirror),
+            Some("move") => {
+                let ship = number(1)?;
+                let to = words.get(2).ok_or(ParsePlacementCommandError::MissingPosition)?;
+                let to = to.parse().map_err(ParsePlacementCommandError::BadPosition)?;
+                Ok(PlacementCommand::Move(ship, to))
+            }
+            Some("mine") => {
+                let cell = words.get(1).ok_or(ParsePlacementCommandError::MissingCell)?;
+                cell.parse().map(PlacementCommand::Mine).map_err(ParsePlacementCommandError::BadPosition)
+            }
+            _ => s.parse().map(PlacementCommand::Place).map_err(ParsePlacementCommandError::BadShip),
+        }
+    }
+}
+
+pub enum ParsePlacementCommandError {
+    BadShip(ParseShipError),
+    BadShipNumber,
+    MissingPosition,
+    MissingCell,
+    BadPosition(ParseShotError),
+}
+
+impl Display for ParsePlacementCommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParsePlacementCommandError::BadShip(inner) => write!(f, "{inner}"),
+            ParsePlacementCommandError::BadShipNumber => write!(f, "which ship? Give its number from 'list'"),
+            ParsePlacementCommandError::MissingPosition => write!(f, "where to? Give the new first cell, like 'move 2 B:4'"),
+            ParsePlacementCommandError::MissingCell => write!(f, "where? Give a cell, like 'mine B:4'"),
+            ParsePlacementCommandError::BadPosition(inner) => write!(f, "can't understand the new position: {inner}"),
+        }
     }
 }
 
 pub enum ParseShipError {
     MissingInfo,
-    ParseIntError(ParseIntError),
-    ParseCharError(ParseCharError),
-    ConversionToCharError,
+    BadShape(ParseShapeError),
+    BadCoordinate(CoordinateError),
     ParseRotationError(ParseRotationError),
+    TooManyCells,
+    /// More follows the rotation of a shaped ship than an `m`.
+    TrailingInput,
+    /// The ends aren't on the same row or column.
+    NotStraight,
 }
 
 impl Display for ParseShipError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ParseShipError::MissingInfo => write!(f, "Some information about the ship is missing!"),
-            ParseShipError::ParseIntError(inner) => write!(f, "Can't understand the x coordinate: {inner}"),
-            ParseShipError::ParseCharError(inner) => write!(f, "Can't understand the y coordinate: {inner}"),
-            ParseShipError::ConversionToCharError => write!(f, "The y coordinate was wrong: coordinate must be in range {FIRST_LETTER}..{LAST_LETTER}"),
+            ParseShipError::BadShape(inner) => write!(f, "Can't understand the shape: {inner}"),
+            ParseShipError::BadCoordinate(inner) => write!(f, "Can't understand where the ship is: {inner}"),
             ParseShipError::ParseRotationError(inner) => write!(f, "The rotation inputted is wrong: {inner}"),
+            ParseShipError::TooManyCells => write!(f, "A ship is given by its two ends, like 'B3-B6'"),
+            ParseShipError::TrailingInput => write!(f, "Only a rotation and 'm' to mirror it can follow the ship's corner"),
+            ParseShipError::NotStraight => write!(f, "The ends of a ship must be on the same row or column"),
         }
     }
 }
@@ -108,7 +370,53 @@ pub enum ParseRotationError {
 impl Display for ParseRotationError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParseRotationError::InvalidInput => write!(f, "rotation must be one of 'v'/'h'"),
+            ParseRotationError::InvalidInput => write!(f, "rotation must be one of 'h'/'v'/'hr'/'vr'"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battleships::coordinate::CoordinateErrorReason;
+
+    fn ship(text: &str) -> Ship {
+        text.parse().unwrap_or_else(|error| panic!("'{text}': {error}"))
+    }
+
+    fn coordinate_error(text: &str) -> CoordinateErrorReason {
+        match text.parse::<Ship>() {
+            Err(ParseShipError::BadCoordinate(error)) => error.reason,
+            Err(error) => panic!("'{text}': {error}"),
+            Ok(ship) => panic!("'{text}' is {ship}"),
+        }
+    }
+
+    #[test]
+    fn reads_ships_by_their_ends() {
+        assert_eq!(ship("B3-B6"), Ship::line(4, 2, 1, Rotation::Horizontal));
+        assert_eq!(ship("B6-B3"), Ship::line(4, 2, 1, Rotation::Horizontal));
+        assert_eq!(ship("b3 b6"), Ship::line(4, 2, 1, Rotation::Horizontal));
+        assert_eq!(ship("B3-E3"), Ship::line(4, 2, 1, Rotation::Vertical));
+        assert_eq!(ship("E7"), Ship::line(1, 6, 4, Rotation::Horizontal));
+        assert_eq!(ship("E7-E7"), Ship::line(1, 6, 4, Rotation::Horizontal));
+        assert_eq!(ship("4:B:3:h"), Ship::line(4, 2, 1, Rotation::Horizontal));
+    }
+
+    #[test]
+    fn refuses_ends_that_make_no_ship() {
+        assert!(matches!("B3-C4".parse::<Ship>(), Err(ParseShipError::NotStraight)));
+        assert!(matches!("B3-B4-B5".parse::<Ship>(), Err(ParseShipError::TooManyCells)));
+    }
+
+    #[test]
+    fn refuses_huge_and_zero_cells() {
+        assert!(matches!(coordinate_error("A1-A18446744073709551615"), CoordinateErrorReason::TooLarge));
+        assert!(matches!(coordinate_error("A18446744073709551615-A18446744073709551616"), CoordinateErrorReason::TooLarge));
+        assert!(matches!(coordinate_error("Z A18446744073709551615 hr"), CoordinateErrorReason::TooLarge));
+        assert!(matches!(coordinate_error("A0-A3"), CoordinateErrorReason::ZeroColumn));
+        assert!(matches!(coordinate_error("T A0 v"), CoordinateErrorReason::ZeroColumn));
+        assert!("4:A:18446744073709551615:h".parse::<Ship>().is_err());
+        assert!("4:A:0:h".parse::<Ship>().is_err());
+    }
+}
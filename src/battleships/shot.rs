@@ -1,8 +1,13 @@
-use std::{fmt::Display, str::FromStr, num::ParseIntError, char::ParseCharError};
+use std::{fmt::Display, str::FromStr};
 
-use super::game_constants::{FIRST_LETTER, LAST_LETTER};
+use super::{
+    coordinate::{self, CoordinateError},
+    weapons::{self, Special, ParseSpecialError},
+};
 use crate::utilities::conversions;
 
+#[derive(Debug)]
+#[derive(Clone, Copy)]
 pub struct Shot { 
     pub x: usize,
     pub y: usize 
@@ -12,49 +17,93 @@ impl FromStr for Shot {
     type Err = ParseShotError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut data = s.split(':');
-
-        let y = match data.next() {
-            Some(value) => value,
-            None => return Err(ParseShotError::MissingInfo),
-        };
-        let y = match y.parse::<char>() {
-            Ok(value) => value,
-            Err(error) => return Err(ParseShotError::ParseCharError(error)),
-        };
-        let y = match conversions::coordinate_to_usize(y) {
-            Some(value) => value,
-            None => return Err(ParseShotError::ConversionError),
-        };
-
-        let x = match data.next() {
-            Some(value) => value,
-            None => return Err(ParseShotError::MissingInfo),
-        };
-        let x = match x.parse::<usize>() {
-            Ok(value) => value,
-            Err(error) => return Err(ParseShotError::ParseIntError(error)),
-        };
-        let x = x - 1;
-
-        Ok(Shot { x, y })
+        coordinate::parse(s).map_err(ParseShotError::BadCoordinate)
     }
 }
 
+impl Display for Shot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", conversions::usize_to_label(self.y), self.x + 1)
+    }
+}
+
+/// Everything that can be typed at the shot prompt.
+pub enum ShotCommand {
+    /// One shot, or a whole volley separated by spaces or commas.
+    Shoot(Vec<Shot>),
+    /// Shots ending with '!', fired even where no ship can be.
+    ForcedShoot(Vec<Shot>),
+    /// A special attack, like 'bomb B:3', 'torpedo C >' or 'sonar E:5'.
+    Special(Special),
+    /// Ask where to shoot next.
+    Hint,
+    /// Ask where to shoot next, and show how likely every cell is to hide a ship.
+    HeatMap,
+    /// Write the game to this file.
+    Save(String),
+    /// Continue the game stored in this file instead.
+    Load(String),
+    /// Take the last shot back.
+    Undo,
+    /// Fire the shot taken back again.
+    Redo,
+}
+
+impl FromStr for ShotCommand {
+    type Err = ParseShotError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("save ") {
+            return Ok(ShotCommand::Save(path.trim().to_string()));
+        }
+        if let Some(path) = s.strip_prefix("load ") {
+            return Ok(ShotCommand::Load(path.trim().to_string()));
+        }
+
+        match s {
+            "hint" => Ok(ShotCommand::Hint),
+            "hint heat" | "heat" => Ok(ShotCommand::HeatMap),
+            "undo" => Ok(ShotCommand::Undo),
+            "redo" => Ok(ShotCommand::Redo),
+            "save" | "load" => Err(ParseShotError::MissingFile),
+            _ if weapons::names_weapon(s) => {
+                s.parse().map(ShotCommand::Special).map_err(ParseShotError::BadSpecial)
+            }
+            _ => match s.strip_suffix('!') {
+                Some(shots) => parse_volley(shots.trim()).map(ShotCommand::ForcedShoot),
+                None => parse_volley(s).map(ShotCommand::Shoot),
+            },
+        }
+    }
+}
+
+/// The shots of a volley. Coordinates may have spaces inside, like `A 5`,
+/// so the whole text is tried as a single shot first.
+fn parse_volley(s: &str) -> Result<Vec<Shot>, ParseShotError> {
+    let single = match s.parse() {
+        Ok(shot) => return Ok(vec![shot]),
+        Err(error) => error,
+    };
+
+    let parts: Vec<&str> = s.split([' ', ',']).filter(|part| !part.is_empty()).collect();
+    if parts.len() < 2 {
+        return Err(single);
+    }
+    parts.into_iter().map(str::parse).collect()
+}
+
 pub enum ParseShotError {
-    MissingInfo,
-    ParseIntError(ParseIntError),
-    ParseCharError(ParseCharError),
-    ConversionError,
+    BadCoordinate(CoordinateError),
+    MissingFile,
+    BadSpecial(ParseSpecialError),
 }
 
 impl Display for ParseShotError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParseShotError::MissingInfo => write!(f, "missing some info about the shot. Maybe you forgot a ':'?"),
-            ParseShotError::ParseIntError(inner) => write!(f, "can't understand the x coordinate: {inner}"),
-            ParseShotError::ParseCharError(inner) => write!(f, "can't understand the y coordinate: {inner}"),
-            ParseShotError::ConversionError => write!(f, "the y coordinate is invalid: coordinate must be in range: {FIRST_LETTER}..{LAST_LETTER}"),
+            ParseShotError::BadCoordinate(inner) => write!(f, "{inner}"),
+            ParseShotError::MissingFile => write!(f, "which file? Type it after the command, like 'save game.txt'"),
+            ParseShotError::BadSpecial(inner) => write!(f, "{inner}"),
         }
     }
 }
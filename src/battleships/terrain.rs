@@ -0,0 +1,100 @@
+//! Islands and mines for the terrain variant.
+//!
+//! Islands are land that both players can see. They are the same on both
+//! fields, so neither side gets the better ground, and no ship can be
+//! placed or shot there. Mines are hidden by every player on their own
+//! field. A shot at a mine damages the shooter's ship closest to where
+//! the mine was.
+
+use super::{
+    feasibility::{self, FeasibilityError},
+    rules::{Rules, FieldSize},
+    ship::Ship,
+    shot::Shot,
+};
+use crate::utilities::random::Random;
+
+/// How many cells an island has at most.
+const LARGEST_ISLAND: usize = 4;
+/// How many sets of islands are tried before giving up on the fleet.
+const ATTEMPTS: usize = 50;
+
+/// `cells` island cells in small clusters, spread over the field of
+/// `rules` so that its fleet still fits around them.
+pub fn generate_islands(rules: &Rules, cells: usize, random: &mut Random) -> Result<Vec<(usize, usize)>, FeasibilityError> {
+    let FieldSize { width, height } = rules.size;
+    if cells > rules.size.cells() {
+        return Err(FeasibilityError::FleetTooLarge { needed: cells, available: rules.size.cells() });
+    }
+
+    let mut last_error = FeasibilityError::NoLayout;
+    for _ in 0..ATTEMPTS {
+        let mut islands: Vec<(usize, usize)> = Vec::with_capacity(cells);
+
+        while islands.len() < cells {
+            // Every island starts on open water and grows along its edges.
+            let mut cell = (random.below(width), random.below(height));
+            if islands.contains(&cell) {
+                continue;
+            }
+            let size = (1 + random.below(LARGEST_ISLAND)).min(cells - islands.len());
+            islands.push(cell);
+
+            for _ in 1..size {
+                let (x, y) = cell;
+                let next = match random.below(4) {
+                    0 if x > 0 => (x - 1, y),
+                    1 if x + 1 < width => (x + 1, y),
+                    2 if y > 0 => (x, y - 1),
+                    3 if y + 1 < height => (x, y + 1),
+                    _ => continue,
+                };
+                if !islands.contains(&next) {
+                    islands.push(next);
+                    cell = next;
+                }
+            }
+        }
+
+        let trial = Rules { islands: islands.clone(), ..rules.clone() };
+        match feasibility::solve(&trial) {
+            Ok(_) => return Ok(islands),
+            Err(error) => last_error = error,
+        }
+    }
+
+    Err(last_error)
+}
+
+/// Where the mines of `rules` go on a field with `ships` on it: random
+/// cells of open water.
+pub fn random_mines(rules: &Rules, ships: &[Ship], random: &mut Random) -> Vec<Shot> {
+    let FieldSize { width, height } = rules.size;
+    let mut water: Vec<Shot> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|cell| !rules.islands.contains(cell))
+        .filter(|cell| !ships.iter().any(|ship| ship.cells().any(|ship_cell| ship_cell == *cell)))
+        .map(|(x, y)| Shot { x, y })
+        .collect();
+
+    random.shuffle(&mut water);
+    water.truncate(rules.mines);
+    water
+}
+
+/// Cells written the way shots are, like `C:4 C:5`.
+pub fn format_cells(cells: impl IntoIterator<Item = (usize, usize)>) -> String {
+    let cells: Vec<String> = cells.into_iter().map(|(x, y)| Shot { x, y }.to_string()).collect();
+    cells.join(" ")
+}
+
+/// Reads cells written by `format_cells`, if they are all on a field of
+/// `size`.
+pub fn parse_cells(text: &str, size: FieldSize) -> Option<Vec<(usize, usize)>> {
+    text.split_whitespace()
+        .map(|cell| match cell.parse::<Shot>() {
+            Ok(Shot { x, y }) if x < size.width && y < size.height => Some((x, y)),
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,284 @@
+//! Special attacks for the arcade variant. Every player starts with a few
+//! of each kind, and using one takes the whole turn:
+//!
+//! - a bomb fires at every cell of the 3×3 square around a cell,
+//! - a torpedo runs along a row from one edge until it meets a ship,
+//! - a sonar ping counts the ship cells in the 3×3 square around a cell,
+//!   without harming them.
+
+use std::{fmt::Display, str::FromStr};
+
+use super::{
+    coordinate::{self, CoordinateError},
+    game_constants::MAX_WEAPONS,
+    shot::Shot,
+};
+use crate::utilities::conversions;
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Weapon {
+    Bomb,
+    Torpedo,
+    Sonar,
+}
+
+impl Display for Weapon {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Weapon::Bomb => write!(f, "bomb"),
+            Weapon::Torpedo => write!(f, "torpedo"),
+            Weapon::Sonar => write!(f, "sonar"),
+        }
+    }
+}
+
+/// Which way a torpedo runs along its row, written as the arrow it
+/// follows.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Heading {
+    /// From the first column on, written `>`.
+    East,
+    /// From the last column back, written `<`.
+    West,
+}
+
+impl Display for Heading {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Heading::East => write!(f, ">"),
+            Heading::West => write!(f, "<"),
+        }
+    }
+}
+
+/// A special attack and where it goes. Written like `bomb B:3`,
+/// `torpedo C >` (or `torpedo C <`, `torpedo C` runs east) and
+/// `sonar E:5`.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub enum Special {
+    Bomb(Shot),
+    Torpedo { row: usize, heading: Heading },
+    Sonar(Shot),
+}
+
+impl Special {
+    pub fn weapon(&self) -> Weapon {
+        match self {
+            Special::Bomb(_) => Weapon::Bomb,
+            Special::Torpedo { .. } => Weapon::Torpedo,
+            Special::Sonar(_) => Weapon::Sonar,
+        }
+    }
+}
+
+impl Display for Special {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Special::Bomb(centre) => write!(f, "bomb {centre}"),
+            Special::Torpedo { row, heading } => write!(f, "torpedo {} {heading}", conversions::usize_to_label(*row)),
+            Special::Sonar(centre) => write!(f, "sonar {centre}"),
+        }
+    }
+}
+
+impl FromStr for Special {
+    type Err = ParseSpecialError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (weapon, target) = s.trim().split_once(' ').ok_or(ParseSpecialError::MissingTarget)?;
+        let target = target.trim();
+
+        match weapon {
+            "bomb" => coordinate::parse(target).map(Special::Bomb).map_err(ParseSpecialError::BadCell),
+            "sonar" => coordinate::parse(target).map(Special::Sonar).map_err(ParseSpecialError::BadCell),
+            "torpedo" => {
+                let (row, heading) = match target.strip_suffix('<') {
+                    Some(row) => (row, Heading::West),
+                    None => (target.strip_suffix('>').unwrap_or(target), Heading::East),
+                };
+                let row = conversions::label_to_usize(row.trim()).ok_or(ParseSpecialError::BadRow)?;
+                Ok(Special::Torpedo { row, heading })
+            }
+            _ => Err(ParseSpecialError::UnknownWeapon),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseSpecialError {
+    UnknownWeapon,
+    MissingTarget,
+    BadCell(CoordinateError),
+    BadRow,
+}
+
+impl Display for ParseSpecialError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseSpecialError::UnknownWeapon => write!(f, "the weapons are 'bomb', 'torpedo' and 'sonar'"),
+            ParseSpecialError::MissingTarget => write!(f, "say where to aim, like 'bomb B:3' or 'torpedo C >'"),
+            ParseSpecialError::BadCell(inner) => write!(f, "{inner}"),
+            ParseSpecialError::BadRow => write!(f, "a torpedo needs a row letter, like 'torpedo C >'"),
+        }
+    }
+}
+
+/// Whether `text` starts with the name of a weapon, so it's meant as a
+/// special attack and not as a shot.
+pub fn names_weapon(text: &str) -> bool {
+    text.split_whitespace().next().is_some_and(|word| ["bomb", "torpedo", "sonar"].contains(&word))
+}
+
+/// Whatever a player fired on a turn: one shot of a volley, or a special
+/// attack.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub enum Attack {
+    Shot(Shot),
+    Special(Special),
+}
+
+impl Display for Attack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Attack::Shot(shot) => write!(f, "{shot}"),
+            Attack::Special(special) => write!(f, "{special}"),
+        }
+    }
+}
+
+impl FromStr for Attack {
+    type Err = ParseSpecialError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if names_weapon(s) {
+            s.parse().map(Attack::Special)
+        } else {
+            coordinate::parse(s).map(Attack::Shot).map_err(ParseSpecialError::BadCell)
+        }
+    }
+}
+
+/// How many special attacks of every kind a player has. Written as
+/// `bombs,torpedoes,sonars`, like `1,2,1`.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+#[derive(Default)]
+pub struct Arsenal {
+    pub bombs: usize,
+    pub torpedoes: usize,
+    pub sonars: usize,
+}
+
+impl Arsenal {
+    pub fn count(&self, weapon: Weapon) -> usize {
+        match weapon {
+            Weapon::Bomb => self.bombs,
+            Weapon::Torpedo => self.torpedoes,
+            Weapon::Sonar => self.sonars,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bombs == 0 && self.torpedoes == 0 && self.sonars == 0
+    }
+
+    /// Uses up one `weapon`. Returns `false` if there was none left.
+    pub fn take(&mut self, weapon: Weapon) -> bool {
+        let count = match weapon {
+            Weapon::Bomb => &mut self.bombs,
+            Weapon::Torpedo => &mut self.torpedoes,
+            Weapon::Sonar => &mut self.sonars,
+        };
+        match count.checked_sub(1) {
+            Some(left) => {
+                *count = left;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// What's left, for the players to read, like "1 bomb, 2 torpedoes".
+    pub fn describe(&self) -> String {
+        let kinds = [(self.bombs, "bomb", "bombs"), (self.torpedoes, "torpedo", "torpedoes"), (self.sonars, "sonar", "sonars")];
+        let left: Vec<String> = kinds.iter()
+            .filter(|(count, ..)| *count > 0)
+            .map(|&(count, one, many)| format!("{count} {}", if count == 1 { one } else { many }))
+            .collect();
+        if left.is_empty() { "none".to_string() } else { left.join(", ") }
+    }
+}
+
+impl Display for Arsenal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{},{}", self.bombs, self.torpedoes, self.sonars)
+    }
+}
+
+impl FromStr for Arsenal {
+    type Err = ParseArsenalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let counts = s.split(',')
+            .map(|count| count.trim().parse::<usize>().map_err(|_| ParseArsenalError::InvalidInput))
+            .collect::<Result<Vec<usize>, _>>()?;
+
+        match counts[..] {
+            [..] if counts.iter().any(|&count| count > MAX_WEAPONS) => Err(ParseArsenalError::TooMany),
+            [bombs, torpedoes, sonars] => Ok(Arsenal { bombs, torpedoes, sonars }),
+            _ => Err(ParseArsenalError::InvalidInput),
+        }
+    }
+}
+
+pub enum ParseArsenalError {
+    InvalidInput,
+    TooMany,
+}
+
+impl Display for ParseArsenalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseArsenalError::InvalidInput => write!(f, "the arsenal must look like '1,2,1' (bombs, torpedoes, sonars)"),
+            ParseArsenalError::TooMany => write!(f, "a player can have at most {MAX_WEAPONS} of every special attack"),
+        }
+    }
+}
+
+/// What a sonar ping found: how many cells of the 3×3 square around
+/// `centre` hold a ship, hit or not.
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+pub struct SonarReading {
+    pub centre: Shot,
+    pub ship_cells: usize,
+}
+
+impl Display for SonarReading {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cells = if self.ship_cells == 1 { "cell" } else { "cells" };
+        write!(f, "{} ship {cells} around {}", self.ship_cells, self.centre)
+    }
+}
+
+/// The cells of the 3×3 square around `centre` that are on the field.
+pub fn area(centre: Shot, width: usize, height: usize) -> impl Iterator<Item = (usize, usize)> {
+    let xs = centre.x.saturating_sub(1)..=(centre.x + 1).min(width - 1);
+    let ys = centre.y.saturating_sub(1)..=(centre.y + 1).min(height - 1);
+    ys.flat_map(move |y| xs.clone().map(move |x| (x, y)))
+}
+
+/// The cells a torpedo passes on `row`, in order, on a field `width` wide.
+pub fn torpedo_path(row: usize, heading: Heading, width: usize) -> impl Iterator<Item = (usize, usize)> {
+    (0..width).map(move |step| match heading {
+        Heading::East => (step, row),
+        Heading::West => (width - 1 - step, row),
+    })
+}
@@ -0,0 +1,199 @@
+use std::{env, time::{Duration, Instant}};
+
+use battleships::{
+    battleships::{
+        bitboard::{BitPlayerField, BitCheckField},
+        field::{PlayerField, CheckField, FieldCell, CheckedCell, ShipPlacementError},
+        ship::{Ship, Rotation},
+        game_constants::{FIELD_SIZE, FLEET},
+    },
+    utilities::random::Random,
+};
+
+const DEFAULT_GAMES: usize = 20_000;
+const SEED: u64 = 0x5EED;
+
+/// The part of the field API the benchmark exercises, so the same
+/// simulation runs against both representations.
+trait Board {
+    fn new(width: usize, height: usize) -> Self;
+    fn at(&self, x: usize, y: usize) -> FieldCell;
+    fn can_place(&self, ship: &Ship) -> Result<(), ShipPlacementError>;
+    fn place_ship(&mut self, ship: &Ship);
+    fn will_ship_survive_after_shot(&self, ship: &Ship, x: usize, y: usize) -> bool;
+    fn mark_dead(&mut self, ship: &Ship);
+    fn mark_hit(&mut self, x: usize, y: usize);
+    fn mark_enemy_miss(&mut self, x: usize, y: usize);
+    fn has_ships_left(&self) -> bool;
+}
+
+trait Tracker {
+    fn new(width: usize, height: usize) -> Self;
+    fn mark(&mut self, x: usize, y: usize, marker: CheckedCell);
+    fn mark_sunk(&mut self, ship: &Ship);
+}
+
+macro_rules! forward_board {
+    ($type:ty) => {
+        impl Board for $type {
+            fn new(width: usize, height: usize) -> Self { <$type>::new(width, height) }
+            fn at(&self, x: usize, y: usize) -> FieldCell { <$type>::at(self, x, y) }
+            fn can_place(&self, ship: &Ship) -> Result<(), ShipPlacementError> { <$type>::can_place(self, ship) }
+            fn place_ship(&mut self, ship: &Ship) { <$type>::place_ship(self, ship) }
+            fn will_ship_survive_after_shot(&self, ship: &Ship, x: usize, y: usize) -> bool { <$type>::will_ship_survive_after_shot(self, ship, x, y) }
+            fn mark_dead(&mut self, ship: &Ship) { <$type>::mark_dead(self, ship) }
+            fn mark_hit(&mut self, x: usize, y: usize) { <$type>::mark_hit(self, x, y) }
+            fn mark_enemy_miss(&mut self, x: usize, y: usize) { <$type>::mark_enemy_miss(self, x, y) }
+            fn has_ships_left(&self) -> bool { <$type>::has_ships_left(self) }
+        }
+    };
+}
+
+macro_rules! forward_tracker {
+    ($type:ty) => {
+        impl Tracker for $type {
+            fn new(width: usize, height: usize) -> Self { <$type>::new(width, height) }
+            fn mark(&mut self, x: usize, y: usize, marker: CheckedCell) { <$type>::mark(self, x, y, marker) }
+            fn mark_sunk(&mut self, ship: &Ship) { <$type>::mark_sunk(self, ship) }
+        }
+    };
+}
+
+forward_board!(PlayerField);
+forward_board!(BitPlayerField);
+forward_tracker!(CheckField);
+forward_tracker!(BitCheckField);
+
+struct Report {
+    layouts: Duration,
+    games: Duration,
+    shots: usize,
+}
+
+fn main() {
+    let games = env::args()
+        .nth(1)
+        .and_then(|count| count.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_GAMES);
+
+    println!("Simulating {games} random games on a {FIELD_SIZE}x{FIELD_SIZE} field...");
+
+    let vec = simulate::<PlayerField, CheckField>(games);
+    let bit = simulate::<BitPlayerField, BitCheckField>(games);
+
+    assert_eq!(vec.shots, bit.shots, "both fields must play out identical games");
+
+    println!("{:<10}{:>14}{:>14}{:>16}", "field", "layouts", "games", "shots/sec");
+    print_report("Vec<Vec>", &vec);
+    print_report("bitboard", &bit);
+
+    let speedup = vec.games.as_secs_f64() / bit.games.as_secs_f64();
+    println!("The bitboard plays games {speedup:.2}x as fast.");
+}
+
+fn print_report(name: &str, report: &Report) {
+    let shots_per_second = report.shots as f64 / report.games.as_secs_f64();
+    println!(
+        "{name:<10}{:>12.1}ms{:>12.1}ms{:>16.0}",
+        report.layouts.as_secs_f64() * 1000.0,
+        report.games.as_secs_f64() * 1000.0,
+        shots_per_second,
+    );
+}
+
+/// Plays `games` games where random fleets are shot at in random order.
+/// The seed is fixed so both representations see exactly the same games.
+fn simulate<B: Board, T: Tracker>(games: usize) -> Report {
+    let mut random = Random::from_seed(SEED);
+
+    let start = Instant::now();
+    for _ in 0..games {
+        random_layout::<B>(&mut random);
+    }
+    let layouts = start.elapsed();
+
+    let mut random = Random::from_seed(SEED);
+    let mut cells: Vec<(usize, usize)> = (0..FIELD_SIZE)
+        .flat_map(|x| (0..FIELD_SIZE).map(move |y| (x, y)))
+        .collect();
+    let mut shots = 0;
+
+    let start = Instant::now();
+    for _ in 0..games {
+        let (mut board, fleet) = random_layout::<B>(&mut random);
+        let mut tracker = T::new(FIELD_SIZE, FIELD_SIZE);
+        random.shuffle(&mut cells);
+
+        // Same order of operations as `Player::shoot`.
+        for &(x, y) in &cells {
+            shots += 1;
+            match take_damage(&mut board, &fleet, x, y) {
+                CheckedCell::Kill => tracker.mark_sunk(ship_at(&fleet, x, y)),
+                result => tracker.mark(x, y, result),
+            }
+            if !board.has_ships_left() {
+                break;
+            }
+        }
+    }
+    let games = start.elapsed();
+
+    Report { layouts, games, shots }
+}
+
+/// Mirrors `Player::take_damage`.
+fn take_damage<B: Board>(board: &mut B, fleet: &[Ship], x: usize, y: usize) -> CheckedCell {
+    if board.at(x, y) != FieldCell::Ship {
+        board.mark_enemy_miss(x, y);
+        return CheckedCell::Miss;
+    }
+
+    let ship = ship_at(fleet, x, y);
+    if board.will_ship_survive_after_shot(ship, x, y) {
+        board.mark_hit(x, y);
+        CheckedCell::Hit
+    } else {
+        board.mark_dead(ship);
+        CheckedCell::Kill
+    }
+}
+
+fn ship_at(fleet: &[Ship], x: usize, y: usize) -> &Ship {
+    fleet.iter()
+        .find(|ship| ship.cells().any(|cell| cell == (x, y)))
+        .expect("every ship cell belongs to a placed ship")
+}
+
+fn random_layout<B: Board>(random: &mut Random) -> (B, Vec<Ship>) {
+    'retry: loop {
+        let mut board = B::new(FIELD_SIZE, FIELD_SIZE);
+        let mut fleet = Vec::with_capacity(FLEET.len());
+
+        for shape in FLEET {
+            let mut attempts = 0;
+            loop {
+                attempts += 1;
+                if attempts > 1000 {
+                    continue 'retry;
+                }
+
+                let rotation = if random.coin() { Rotation::Horizontal } else { Rotation::Vertical };
+                let ship = Ship {
+                    shape,
+                    x: random.below(FIELD_SIZE),
+                    y: random.below(FIELD_SIZE),
+                    rotation,
+                    mirrored: false,
+                };
+
+                if board.can_place(&ship).is_ok() {
+                    board.place_ship(&ship);
+                    fleet.push(ship);
+                    break;
+                }
+            }
+        }
+
+        return (board, fleet);
+    }
+}
@@ -1,45 +1,566 @@
-use std::collections::HashMap;
+use std::{net::TcpStream, time::{Duration, Instant, SystemTime}};
 
 use crate::{
-    battleships::{player::Player, game_constants::SHIP_COUNT, ship::Ship, field::ShipPlacementError, shot::Shot}, 
-    utilities::{input, pretty_output}
+    battleships::{
+        player::{Outcome, Player, Overlay},
+        shape::Shape,
+        ship::{Ship, PlacementCommand},
+        field::CheckedCell,
+        shot::{Shot, ShotCommand},
+        game::{Answer, Game, Side, Phase, Command, GameError},
+        events::Event,
+        rules::{Rules, FieldSize, Salvo},
+        weapons::{Arsenal, Attack, Special},
+        replay::Recorder,
+        feasibility::{self, FeasibilityError},
+        ai::monte_carlo::{MonteCarloShooter, Budget, default_threads},
+    },
+    utilities::{input, pretty_output, unsafe_net::{self, NetError}}
 };
 
-pub fn read_shot(player: &mut Player) -> Shot {
-    pretty_output::clear_screen();
-    player.print();
-    input::read_safe::<Shot>("Input a shot 'y:x'")
+/// How many layouts the advisor samples for a hint.
+const HINT_SAMPLES: usize = 2000;
+
+/// What the player on turn decided to do.
+pub enum Action {
+    /// They fired these shots, and the game accepted them.
+    Shoot(Vec<Shot>),
+    /// They used a special attack, which the game accepted. The report
+    /// says what it did.
+    Special { special: Special, report: String },
+    /// They want the game written to this file.
+    Save(String),
+    /// They want to continue the game stored in this file.
+    Load(String),
+    /// They want their last shot back.
+    Undo,
+    /// They want to fire the shot they took back again.
+    Redo,
+}
+
+/// Reads commands from the player on `side` until the game accepts a shot
+/// or special attack, or they ask to save or load. `notice` is shown above the prompt first.
+pub fn take_turn(game: &mut Game, side: Side, notice: Option<String>) -> Action {
+    let mut notice = with_shoot_again(game, notice);
+
+    loop {
+        let shots_left = game.volley_size(side) - game.pending_shots().len();
+        let shots = match read_shot(game.player(side), &game.rules().fleet, shots_left, game.arsenal(side), notice.take()) {
+            Action::Shoot(shots) => shots,
+            Action::Special { special, .. } => match game.handle(Command::UseSpecial(side, special)) {
+                Ok(events) => return Action::Special { special, report: special_report(side, special, &events) },
+                Err(error) => {
+                    notice = Some(format!("WARNING: {error}"));
+                    continue;
+                }
+            },
+            action => return action,
+        };
+
+        // A volley is only fired when every shot of it is fine, so that a
+        // typo doesn't leave it half fired.
+        if let Err(message) = check_volley(game, side, &shots) {
+            notice = Some(format!("WARNING: {message}"));
+            continue;
+        }
+        for &shot in &shots {
+            game.handle(Command::Shoot(side, shot)).expect("checked with the game");
+        }
+        return Action::Shoot(shots);
+    }
+}
+
+/// `notice`, followed by a word that the player on turn shoots again if
+/// they hit with their last volley.
+fn with_shoot_again(game: &Game, notice: Option<String>) -> Option<String> {
+    if !game.shoots_again() {
+        return notice;
+    }
+    Some(match notice {
+        Some(notice) => format!("{notice}\nYou hit, so it's your turn again."),
+        None => "You hit, so it's your turn again.".to_string(),
+    })
+}
+
+fn check_volley(game: &Game, side: Side, shots: &[Shot]) -> Result<(), GameError> {
+    for (index, &shot) in shots.iter().enumerate() {
+        game.can_shoot(side, shot)?;
+        if shots[..index].iter().any(|other| other.x == shot.x && other.y == shot.y) {
+            return Err(GameError::AlreadyAimed);
+        }
+    }
+    Ok(())
+}
+
+/// Reads what the player wants to do, answering requests for hints on the
+/// way. It asks for `shots` shots at once, or one of the special attacks
+/// in `arsenal`, which haven't been fired yet when they are returned.
+/// The hints look for the ships of `fleet`.
+pub fn read_shot(player: &Player, fleet: &[Shape], shots: usize, arsenal: Arsenal, mut notice: Option<String>) -> Action {
+    let mut overlay = Overlay::default();
+
+    loop {
+        pretty_output::clear_screen();
+        player.print_with_overlay(&overlay);
+        if let Some(shot) = overlay.highlight {
+            println!("Hint: try {shot} (marked with @)");
+        }
+        let readings: Vec<String> = player.checked_field().sonar_readings().iter()
+            .map(|reading| format!("{} found {}", reading.centre, reading.ship_cells))
+            .collect();
+        if !readings.is_empty() {
+            println!("Sonar: {}", readings.join(", "));
+        }
+        if !arsenal.is_empty() {
+            println!("Weapons left: {}", arsenal.describe());
+        }
+        if let Some(notice) = notice.take() {
+            println!("{notice}");
+        }
+
+        let mut message = if shots == 1 {
+            "Input a shot 'y:x'".to_string()
+        } else {
+            format!("Input {shots} shots like 'A:1 B:4'")
+        };
+        if !arsenal.is_empty() {
+            message.push_str(", a weapon like 'bomb y:x'/'torpedo y >'/'sonar y:x'");
+        }
+        message.push_str(", 'hint'/'hint heat' for advice, or 'save <file>'/'load <file>'");
+        let command = input::read_safe::<ShotCommand>(&message);
+        match command {
+            ShotCommand::Shoot(volley) | ShotCommand::ForcedShoot(volley) if volley.len() != shots => {
+                notice = Some(format!("WARNING: expected {shots} shots, got {}", volley.len()));
+            }
+            ShotCommand::Shoot(volley) if volley.iter().any(|&shot| is_known_empty(player, shot)) => {
+                let shot = volley.iter().find(|&&shot| is_known_empty(player, shot)).expect("checked above");
+                let typed: Vec<String> = volley.iter().map(|shot| shot.to_string()).collect();
+                notice = Some(format!(
+                    "WARNING: no ship can be at {shot} (marked with .). Type '{}!' to shoot there anyway.", typed.join(" ")
+                ));
+            }
+            ShotCommand::Shoot(volley) | ShotCommand::ForcedShoot(volley) => return Action::Shoot(volley),
+            ShotCommand::Special(special) if arsenal.count(special.weapon()) == 0 => {
+                notice = Some(format!("WARNING: there's no {} left", special.weapon()));
+            }
+            ShotCommand::Special(special) => return Action::Special { special, report: String::new() },
+            ShotCommand::Hint => overlay = hint(player, fleet, false),
+            ShotCommand::HeatMap => overlay = hint(player, fleet, true),
+            ShotCommand::Save(path) => return Action::Save(path),
+            ShotCommand::Load(path) => return Action::Load(path),
+            ShotCommand::Undo => return Action::Undo,
+            ShotCommand::Redo => return Action::Redo,
+        }
+    }
+}
+
+fn is_known_empty(player: &Player, shot: Shot) -> bool {
+    let checked = player.checked_field();
+    shot.x < checked.width() && shot.y < checked.height() && checked.at(shot.x, shot.y) == CheckedCell::KnownEmpty
+}
+
+/// Asks the Monte-Carlo AI what it would do in the player's place.
+fn hint(player: &Player, fleet: &[Shape], with_heat: bool) -> Overlay {
+    let mut advisor = MonteCarloShooter::new(Budget::Samples(HINT_SAMPLES), default_threads());
+    advisor.set_fleet(fleet);
+    let (shot, heat) = advisor.analyse(player.checked_field());
+
+    // Stretch the possible cells over the whole scale, so that even a
+    // flat looking distribution shows where the hot spots are.
+    let heat = with_heat.then(|| {
+        let coldest = heat.iter().copied().filter(|count| *count > 0).min().unwrap_or(0);
+        let hottest = heat.iter().copied().max().unwrap_or(0);
+        let spread = (hottest - coldest).max(1) as f64;
+
+        heat.iter().map(|&count| {
+            if count == 0 { 0.0 } else { 0.25 + 0.75 * (count - coldest) as f64 / spread }
+        }).collect()
+    });
+
+    Overlay { highlight: Some(shot), heat }
+}
+
+/// Lets the player on `side` set up their fleet until they confirm it.
+/// Placed ships can be listed, removed, moved and rotated, and if the game
+/// allows it, changes can be taken back.
+pub fn place_ships(game: &mut Game, side: Side) {
+    let mut notice: Option<String> = None;
+
+    while !game.is_fleet_confirmed(side) {
+        pretty_output::clear_screen();
+        game.player(side).print();
+
+        let mut left = game.ships_to_place(side).to_vec();
+        left.sort_by_key(|shape| (std::cmp::Reverse(shape.size()), shape.to_string()));
+        let left: Vec<String> = left.iter().map(|shape| shape.to_string()).collect();
+        let mines_left = game.mines_to_place(side);
+        if left.is_empty() && mines_left == 0 {
+            println!("All ships are placed. Type 'done' to confirm the fleet.");
+        } else if !left.is_empty() {
+            println!("Ships left to place: {}", left.join(", "));
+        }
+        if mines_left > 0 {
+            println!("Mines left to hide: {mines_left}");
+        }
+        if let Some(notice) = notice.take() {
+            println!("{notice}");
+        }
+
+        let mut message = "Input a ship by its ends like 'B3-B6' (or 'E7' for one cell), or 'list', 'remove <n>', 'move <n> <y:x>', 'rotate <n>', 'clear', 'done'".to_string();
+        if game.rules().fleet.iter().any(|shape| !shape.is_straight()) {
+            message.push_str(", a shaped ship by its corner like 'L B3 v' (add 'm' to mirror it), 'mirror <n>'");
+        }
+        if game.rules().mines > 0 {
+            message.push_str(", 'mine <y:x>'");
+        }
+        if game.undo_allowed() {
+            message.push_str(", 'undo', 'redo'");
+        }
+        let command = input::read_safe::<PlacementCommand>(&message);
+
+        let result = match command {
+            PlacementCommand::Place(ship) => game.handle(Command::PlaceShip(side, ship)).map(|_| ()),
+            PlacementCommand::List => {
+                notice = Some(list_ships(game.player(side)));
+                Ok(())
+            }
+            PlacementCommand::Remove(number) => placed_ship(game, side, number)
+                .and_then(|ship| game.handle(Command::RemoveShip(side, ship)).map(|_| ())),
+            PlacementCommand::Move(number, to) => placed_ship(game, side, number).and_then(|from| {
+                let to = Ship { x: to.x, y: to.y, ..from };
+                game.handle(Command::MoveShip { side, from, to }).map(|_| ())
+            }),
+            PlacementCommand::Rotate(number) => placed_ship(game, side, number).and_then(|from| {
+                game.handle(Command::MoveShip { side, from, to: from.turned() }).map(|_| ())
+            }),
+            PlacementCommand::Mirror(number) => placed_ship(game, side, number).and_then(|from| {
+                let to = from.mirrored();
+                if to == from.normalised() {
+                    return Err(GameError::Symmetric);
+                }
+                game.handle(Command::MoveShip { side, from, to }).map(|_| ())
+            }),
+            PlacementCommand::Mine(mine) => {
+                let placed = game.player(side).mines().iter().any(|other| other.x == mine.x && other.y == mine.y);
+                let command = if placed { Command::RemoveMine(side, mine) } else { Command::PlaceMine(side, mine) };
+                game.handle(command).map(|_| ())
+            }
+            PlacementCommand::Clear => game.handle(Command::ClearFleet(side)).map(|_| ()),
+            PlacementCommand::Confirm => game.handle(Command::ConfirmFleet(side)).map(|_| ()),
+            // Only this side's own changes can be taken back or made again.
+            PlacementCommand::Undo => match game.last_command() {
+                Some(command) if command.side() == side => game.undo().map(|_| ()),
+                _ => Err(GameError::NothingToUndo),
+            },
+            PlacementCommand::Redo => match game.next_redo() {
+                Some(command) if command.side() == side => game.redo().map(|_| ()),
+                _ => Err(GameError::NothingToRedo),
+            },
+        };
+        if let Err(error) = result {
+            notice = Some(format!("WARNING: {error}"));
+        }
+    }
+
+    println!("Your ship placement:");
+    game.player(side).print();
+}
+
+/// The `number`th ship `side` placed, counting from 1.
+fn placed_ship(game: &Game, side: Side, number: usize) -> Result<Ship, GameError> {
+    game.player(side).ships().get(number - 1).copied().ok_or(GameError::NoSuchShip)
 }
 
-pub fn place_ships(player: &mut Player) {
-    let mut ships_left = HashMap::new();
-    ships_left.insert(4usize, 1);
-    ships_left.insert(3, 2);
-    ships_left.insert(2, 3);
-    ships_left.insert(1, 4);
+fn list_ships(player: &Player) -> String {
+    let mut list = if player.ships().is_empty() {
+        "No ships are placed yet.".to_string()
+    } else {
+        let ships: Vec<String> = player.ships().iter()
+            .enumerate()
+            .map(|(index, ship)| format!("{}) {ship}", index + 1))
+            .collect();
+        format!("Placed ships: {}", ships.join("  "))
+    };
+    if !player.mines().is_empty() {
+        let mines: Vec<String> = player.mines().iter().map(|mine| mine.to_string()).collect();
+        list.push_str(&format!("\nMines: {}", mines.join(" ")));
+    }
+    list
+}
 
-    for _ in 0..SHIP_COUNT {
+/// Shows how the game ended from the point of view of `side`.
+pub fn print_result(game: &Game, side: Side) {
+    if let Phase::Finished(winner) = game.phase() {
         pretty_output::clear_screen();
-        player.print();
+        if winner == side {
+            println!("You won!");
+        } else {
+            println!("You lost :(");
+        }
+    }
+
+    print_final_fields(game, side);
+}
+
+pub fn print_final_fields(game: &Game, side: Side) {
+    println!("Final game state: ");
+    println!("Your field: ");
+    game.player(side).print();
+    println!("Enemy field: ");
+    game.player(side.other()).print();
+}
+
+/// Records `game` into `replays/` from now on.
+pub fn record(game: &mut Game) {
+    match Recorder::start(game) {
+        Ok(recorder) => game.subscribe(Box::new(recorder)),
+        Err(error) => eprintln!("WARNING: can't record a replay: {error}"),
+    }
+}
+
+/// What the volley `side` just fired did, if that's worth telling: always
+/// when playing with salvos, and whenever a shot set off a mine.
+pub fn shot_report(game: &Game, side: Side, shots: &[Shot]) -> Option<String> {
+    let mine = shots.iter().any(|shot| game.player(side).checked_field().at(shot.x, shot.y) == CheckedCell::Mine);
+    (game.rules().salvo != Salvo::Off || mine).then(|| volley_report(game, side, shots))
+}
+
+/// What every shot of the volley `side` just fired did, like
+/// "Player 1's volley: A:1 hit, B:4 miss".
+pub fn volley_report(game: &Game, side: Side, shots: &[Shot]) -> String {
+    let outcomes: Vec<String> = shots.iter()
+        .map(|shot| {
+            let outcome = match game.player(side).checked_field().at(shot.x, shot.y) {
+                CheckedCell::Hit => "hit",
+                CheckedCell::Kill => "sunk",
+                CheckedCell::Mine => "mine",
+                _ => "miss",
+            };
+            format!("{shot} {outcome}")
+        })
+        .collect();
+    format!("{side}'s volley: {}", outcomes.join(", "))
+}
+
+/// What the special attack `side` just used did, going by the `events` it
+/// caused, like "Player 1's bomb B:3: B:2 miss, B:3 hit".
+pub fn special_report(side: Side, special: Special, events: &[Event]) -> String {
+    let mut outcomes = Vec::new();
+    for event in events {
+        match event {
+            Event::Miss { shot, .. } => outcomes.push(format!("{shot} miss")),
+            Event::Hit { shot, .. } => outcomes.push(format!("{shot} hit")),
+            Event::ShipSunk { shot, .. } => outcomes.push(format!("{shot} sunk")),
+            Event::SonarPing { reading, .. } => outcomes.push(format!("found {reading}")),
+            Event::MineHit { shot, .. } => outcomes.push(format!("{shot} mine")),
+            _ => {}
+        }
+    }
+    format!("{side}'s {special}: {}", outcomes.join(", "))
+}
 
-        let ship: Ship = input::read_while("Input a ship 'length:y:x:rotation': ", |ship| {
-            if let Err(error) = player.can_place(ship) {
-                return Err(error);
+/// Plays a whole game over the network. Both sides run the same `Game`,
+/// but each only knows its own fleet: every attack is sent to the other
+/// side, which answers what it did cell by cell. Once the game is over,
+/// the fleets are shown to each other, so that the answers can be checked
+/// and the replay written. The side that listens picks the `rules` and
+/// sends them over. Fails when the connection breaks or the opponent
+/// sends something that isn't allowed.
+pub fn play_online(stream: &mut TcpStream, local: Side, rules: Option<Rules>) -> Result<(), NetError> {
+    let remote = local.other();
+    let rules = match rules {
+        Some(rules) => {
+            unsafe_net::send_rules(stream, &rules)?;
+            rules
+        }
+        None => {
+            let rules = unsafe_net::read_rules(stream)?;
+            // A fleet that may fit after all is the placing player's problem.
+            match feasibility::solve(&rules) {
+                Ok(_) | Err(FeasibilityError::Undecided) => rules,
+                Err(error) => return Err(NetError::BadMessage(format!("rules the game can't be played with: {error}"))),
             }
+        }
+    };
+    let mut game = Game::new(rules);
+    game.hide_fleet(remote);
+    // The replay can only be written once the fleets are shown, so the
+    // time of every attack is kept until then.
+    let (began, start) = (SystemTime::now(), Instant::now());
+    let mut times: Vec<Duration> = Vec::new();
 
-            if ships_left.get(&ship.length).unwrap_or(&0) == &0 {
-                return Err(ShipPlacementError::NoShipsOfLengthLeft(ship.length));
+    place_ships(&mut game, local);
+    println!("Waiting for the opponent to place their ships...");
+    unsafe_net::send_ready(stream)?;
+    unsafe_net::read_ready(stream)?;
+    game.handle(Command::ConfirmFleet(remote)).expect("a hidden fleet can always be confirmed");
+
+    let mut notice = None;
+    loop {
+        match game.phase() {
+            Phase::Shooting(side) if side == local => notice = take_online_turn(stream, &mut game, local, notice.take())?,
+            Phase::Shooting(_) => {
+                pretty_output::clear_screen();
+                game.player(local).print();
+                if let Some(notice) = &notice {
+                    println!("{notice}");
+                }
+                if game.shoots_again() {
+                    println!("The opponent hit and shoots again...");
+                } else {
+                    println!("Waiting for the opponent's shot...");
+                }
+
+                // Whatever was shown while waiting is old news now.
+                notice = None;
+                let attacks = unsafe_net::read_attacks(stream)?;
+                let answer = game.answer(remote, &attacks)
+                    .map_err(|error| NetError::BadMessage(format!("an invalid attack: {error}")))?;
+                unsafe_net::send_answer(stream, &answer)?;
+                // Only the opponent knows what the mines did to their fleet.
+                let mut told = Answer::default();
+                if answer.cells.iter().any(|(_, outcome)| matches!(outcome, Outcome::Mine)) {
+                    told.damage = unsafe_net::read_damage(stream)?;
+                }
+
+                let mut shots = Vec::new();
+                for attack in attacks {
+                    let events = game.handle_answered(Command::attack(remote, attack), &told)
+                        .map_err(|error| NetError::BadMessage(format!("mine damage that can't be true: {error}")))?;
+                    match attack {
+                        Attack::Shot(shot) => shots.push(shot),
+                        Attack::Special(special) => notice = Some(special_report(remote, special, &events)),
+                    }
+                }
+                if !shots.is_empty() {
+                    notice = shot_report(&game, remote, &shots).or(notice);
+                }
             }
+            Phase::Placement => unreachable!("both fleets are placed"),
+            Phase::Finished(_) => break,
+        }
+        times.resize(game.history().len(), start.elapsed());
+    }
 
+    // The game is decided, so the fleets can't give anything away now.
+    unsafe_net::send_fleet(stream, game.player(local).ships(), game.player(local).mines())?;
+    match unsafe_net::read_fleet(stream).and_then(|(fleet, mines)| revealed_game(&game, remote, &fleet, &mines)) {
+        Ok(revealed) => {
+            if let Err(error) = Recorder::start_played(&revealed, began, &times) {
+                eprintln!("WARNING: can't record a replay: {error}");
+            }
+            print_result(&revealed, local);
             Ok(())
-        });
+        }
+        Err(error) => {
+            print_result(&game, local);
+            Err(error)
+        }
+    }
+}
 
-        *ships_left.get_mut(&ship.length).expect("This shouldn't happen") -= 1;
+/// Reads the turn of the player on `side` like `take_turn` does, and
+/// fires it at the fleet on the other end of `stream`. Returns what it
+/// did, if that's worth telling.
+fn take_online_turn(stream: &mut TcpStream, game: &mut Game, side: Side, notice: Option<String>) -> Result<Option<String>, NetError> {
+    let mut notice = with_shoot_again(game, notice);
+
+    loop {
+        let shots = game.volley_size(side);
+        let attacks = match read_shot(game.player(side), &game.rules().fleet, shots, game.arsenal(side), notice.take()) {
+            Action::Shoot(shots) => match check_volley(game, side, &shots) {
+                Ok(()) => {
+                    unsafe_net::send_volley(stream, &shots)?;
+                    shots.into_iter().map(Attack::Shot).collect()
+                }
+                Err(error) => {
+                    notice = Some(format!("WARNING: {error}"));
+                    continue;
+                }
+            },
+            Action::Special { special, .. } => match game.can_use_special(side, special) {
+                Ok(()) => {
+                    unsafe_net::send_special(stream, special)?;
+                    vec![Attack::Special(special)]
+                }
+                Err(error) => {
+                    notice = Some(format!("WARNING: {error}"));
+                    continue;
+                }
+            },
+            _ => {
+                notice = Some("WARNING: online games can't be saved, loaded or taken back.".to_string());
+                continue;
+            }
+        };
 
-        player.place_ship(&ship);
+        let answer = unsafe_net::read_answer(stream)?;
+        let mut events = Vec::new();
+        for &attack in &attacks {
+            let landed = game.handle_answered(Command::attack(side, attack), &answer)
+                .map_err(|error| NetError::BadMessage(format!("an answer that can't be true: {error}")))?;
+            events.extend(landed);
+        }
+        if answer.cells.iter().any(|(_, outcome)| matches!(outcome, Outcome::Mine)) {
+            unsafe_net::send_damage(stream, &mine_damage(game, side, &events))?;
+        }
+        return Ok(match attacks[..] {
+            [Attack::Special(special)] => Some(special_report(side, special, &events)),
+            _ => {
+                let shots: Vec<Shot> = attacks.iter().filter_map(|attack| match attack {
+                    Attack::Shot(shot) => Some(*shot),
+                    Attack::Special(_) => None,
+                }).collect();
+                shot_report(game, side, &shots)
+            }
+        });
     }
+}
 
-    println!("Your ship placement:");
-    player.print();
+/// What the mines in `events` did to the fleet of `side`, as the mines'
+/// owner is told it.
+fn mine_damage(game: &Game, side: Side, events: &[Event]) -> Vec<Option<(Shot, Outcome)>> {
+    events.iter().filter_map(|event| match *event {
+        Event::MineHit { damaged, sunk, .. } => Some(damaged.map(|cell| {
+            let outcome = match game.player(side).ship_at(cell.x, cell.y) {
+                Some(ship) if sunk => Outcome::Sunk(*ship),
+                _ => Outcome::Hit,
+            };
+            (cell, outcome)
+        })),
+        _ => None,
+    }).collect()
 }
 
+/// The game the fleet of `remote` was hidden in, played again with the
+/// `fleet` and `mines` they showed at the end. Fails if that's not the
+/// fleet they answered for.
+fn revealed_game(game: &Game, remote: Side, fleet: &[Ship], mines: &[Shot]) -> Result<Game, NetError> {
+    let local = remote.other();
+    let local_player = game.player(local);
+    let mut revealed = Game::new(game.rules().clone());
+    revealed.disable_undo();
+    revealed.place_fleet_and_mines(local, local_player.ships(), local_player.mines()).expect("it was placed in the game");
+    revealed.place_fleet_and_mines(remote, fleet, mines)
+        .map_err(|error| NetError::BadMessage(format!("an invalid fleet: {error}")))?;
+
+    let mismatch = || NetError::BadMessage("a fleet that doesn't match what they answered".to_string());
+    for &(side, attack) in game.history() {
+        revealed.handle(Command::attack(side, attack)).map_err(|_| mismatch())?;
+    }
+    let FieldSize { width, height } = game.rules().size;
+    for side in [Side::First, Side::Second] {
+        let (answered, real) = (game.player(side).checked_field(), revealed.player(side).checked_field());
+        let same_cells = (0..height).all(|y| (0..width).all(|x| answered.at(x, y) == real.at(x, y)));
+        let same_pings = answered.sonar_readings().iter()
+            .zip(real.sonar_readings())
+            .all(|(answered, real)| answered.ship_cells == real.ship_cells);
+        if !same_cells || !same_pings {
+            return Err(mismatch());
+        }
+    }
+    if revealed.phase() != game.phase() {
+        return Err(mismatch());
+    }
+    Ok(revealed)
+}
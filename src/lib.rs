@@ -0,0 +1,3 @@
+pub mod battleships;
+pub mod utilities;
+pub mod gameplay;
@@ -1,90 +1,462 @@
-mod battleships;
-mod utilities;
-mod gameplay;
+use std::{fs::{self, OpenOptions}, path::Path, process, time::Duration};
 
 use battleships::{
-    player::{Player, Victory},
-    ship::{Ship, Rotation},
-    shot::Shot,
-    game_constants::FIELD_SIZE,
+    battleships::{
+        game::{Game, Side, Phase, Command, GameError},
+        events::EventLog,
+        save::{self, SavedGame},
+        notation,
+        feasibility::{self, FeasibilityError},
+        terrain,
+        shape::{self, Shape},
+        shot::Shot,
+        rules::{Rules, FieldSize, TouchingPolicy, Salvo, TurnOrder},
+        weapons::{Arsenal, Attack},
+        game_constants::MAX_FIELD_SIZE,
+        ai::{
+            Shooter, Difficulty,
+            monte_carlo::{MonteCarloShooter, Budget},
+            placement::{Placer, AdaptivePlacer},
+            profile::Profile,
+        },
+    },
+    utilities::{args::Args, input, pretty_output, random::Random},
+    gameplay::{self, Action},
 };
 
-use utilities::input;
+/// Who is who in single player games.
+const HUMAN: Side = Side::First;
+const COMPUTER: Side = Side::Second;
 
-fn clear_screen() {
-    print!("\x1B[2J\x1B[1;1H");
+/// The opponent's AI, tuned by `--difficulty`, or precisely with
+/// `--samples`/`--think-ms` and `--threads`.
+fn opponent_shooter(args: &Args, difficulty: Difficulty) -> MonteCarloShooter {
+    let mut budget = difficulty.budget();
+    if let Some(samples) = args.parsed::<usize>("--samples") {
+        budget = Budget::Samples(samples);
+    }
+    if let Some(milliseconds) = args.parsed::<u64>("--think-ms") {
+        budget = Budget::Time(Duration::from_millis(milliseconds));
+    }
+    let threads = args.parsed::<usize>("--threads").unwrap_or_else(|| difficulty.threads());
+
+    MonteCarloShooter::new(budget, threads)
+}
+
+/// The profile of the human named by `--name`, if any. The computer
+/// learns from it and adds every finished game to it.
+fn load_profile(args: &Args, rules: &Rules) -> Option<Profile> {
+    let name = args.value("--name")?;
+
+    match Profile::load(name, rules.size) {
+        Ok(profile) => Some(profile),
+        Err(error) => {
+            eprintln!("WARNING: {error}. Starting a new profile for {name}.");
+            Some(Profile::new(name, rules.size))
+        }
+    }
+}
+
+fn new_game(args: &Args, rules: Rules, against_computer: bool) -> Game {
+    let mut game = Game::new(rules);
+    set_up(args, &mut game, against_computer);
+    game
+}
+
+/// Records `game` as a replay, and appends everything that happens in it
+/// to the `--log` file, if any. Moves can only be taken back against the
+/// computer, and not even there with `--no-undo`.
+fn set_up(args: &Args, game: &mut Game, against_computer: bool) {
+    if !against_computer || args.flag("--no-undo") {
+        game.disable_undo();
+    }
+    gameplay::record(game);
+
+    if let Some(path) = args.value("--log") {
+        match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => game.subscribe(Box::new(EventLog::new(file))),
+            Err(error) => eprintln!("WARNING: can't create the log {path}: {error}"),
+        }
+    }
+}
+
+/// The rules for a new game: the standard ones, on a `--size` field,
+/// which can be rectangular like `12x8`, and with ships as close to each
+/// other as `--touching` allows. `--fleet` swaps the ships, `--arsenal`
+/// hands out special attacks, `--islands` scatters that many cells of
+/// land and `--mines` gives every player mines to hide.
+fn chosen_rules(args: &Args) -> Rules {
+    let mut rules = Rules::default();
+    if let Some(size) = args.parsed::<FieldSize>("--size") {
+        if size.width > MAX_FIELD_SIZE || size.height > MAX_FIELD_SIZE {
+            eprintln!("ERROR: --size can be at most {MAX_FIELD_SIZE} each way");
+            process::exit(1);
+        }
+        rules.size = size;
+    }
+    if let Some(touching) = args.parsed::<TouchingPolicy>("--touching") {
+        rules.touching = touching;
+    }
+    if let Some(fleet) = args.value("--fleet") {
+        rules.fleet = parse_fleet(fleet);
+    }
+    if let Some(salvo) = args.parsed::<Salvo>("--salvo") {
+        if matches!(salvo, Salvo::Fixed(shots) if shots > rules.size.cells()) {
+            eprintln!("ERROR: --salvo can't have more shots than the {} cells of the field", rules.size.cells());
+            process::exit(1);
+        }
+        rules.salvo = salvo;
+    }
+    if let Some(turns) = args.parsed::<TurnOrder>("--turns") {
+        rules.turns = turns;
+    }
+    if let Some(arsenal) = args.parsed::<Arsenal>("--arsenal") {
+        rules.arsenal = arsenal;
+    }
+    if let Some(mines) = args.parsed::<usize>("--mines") {
+        rules.mines = mines;
+    }
+    if let Some(islands) = args.parsed::<usize>("--islands") {
+        match terrain::generate_islands(&rules, islands, &mut Random::new()) {
+            Ok(islands) => rules.islands = islands,
+            Err(error) => {
+                eprintln!("ERROR: can't put {islands} island cells on the field: {error}");
+                process::exit(1);
+            }
+        }
+    }
+    match feasibility::solve(&rules) {
+        Ok(_) => {}
+        // The fleet may still fit, and placing it tells for sure.
+        Err(FeasibilityError::Undecided) => eprintln!("WARNING: {}", FeasibilityError::Undecided),
+        Err(error) => {
+            eprintln!("ERROR: can't play on a {}x{} field: {error}", rules.size.width, rules.size.height);
+            process::exit(1);
+        }
+    }
+    rules
+}
+
+/// The ships of `--fleet`, like `4,3,3,2` or `tetris`.
+fn parse_fleet(fleet: &str) -> Vec<Shape> {
+    match shape::parse_fleet(fleet) {
+        Ok(fleet) => fleet,
+        Err(error) => {
+            eprintln!("ERROR: can't understand --fleet '{fleet}': {error}");
+            process::exit(1);
+        }
+    }
 }
 
 fn main() {
-    let mut player = Player::new(FIELD_SIZE);
-    
-    gameplay::place_ships(&mut player);
-
-    let mut opponent = Player::new(FIELD_SIZE);
-
-    let opponent_ships = vec![
-        Ship { length: 4, x: 0,  y: 0,  rotation: Rotation::Vertical },
-        Ship { length: 3, x: 4,  y: 1,  rotation: Rotation::Horizontal },
-        Ship { length: 3, x: 9, y: 4,  rotation: Rotation::Vertical },
-        Ship { length: 2, x: 0,  y: 9,  rotation: Rotation::Horizontal },
-        Ship { length: 2, x: 4,  y: 3,  rotation: Rotation::Vertical },
-        Ship { length: 2, x: 7,  y: 9,  rotation: Rotation::Horizontal },
-        Ship { length: 1, x: 1,  y: 6,  rotation: Rotation::Horizontal },
-        Ship { length: 1, x: 3,  y: 6,  rotation: Rotation::Horizontal },
-        Ship { length: 1, x: 9, y:  0, rotation: Rotation::Horizontal },
-        Ship { length: 1, x: 6,  y: 5,  rotation: Rotation::Horizontal },
-    ];
-
-    for ship in opponent_ships {
-        opponent.place_ship(&ship);
-    }
-    
-    let mut opponent_shot_y = 0;
-    let mut opponent_shot_x = 0;
+    let args = Args::from_env();
+
+    if let Some(path) = args.value("--resume") {
+        resume(&args, path);
+    } else if let Some(saved) = offer_autosave() {
+        continue_game(&args, saved);
+    } else if args.flag("--hotseat") {
+        hotseat(&args, chosen_rules(&args));
+    } else {
+        single_player(&args, chosen_rules(&args));
+    }
+}
+
+fn single_player(args: &Args, rules: Rules) {
+    let profile = load_profile(args, &rules);
+    let difficulty = args.parsed::<Difficulty>("--difficulty").unwrap_or(Difficulty::Normal);
+    let mut opponent_ai = opponent_shooter(args, difficulty);
+    opponent_ai.set_fleet(&rules.fleet);
+
+    let mut opponent_placer: Box<dyn Placer> = match &profile {
+        Some(profile) if profile.games() > 0 => {
+            opponent_ai.set_prior(profile.ship_prior(), profile.confidence());
+            Box::new(AdaptivePlacer::new(profile))
+        }
+        _ => difficulty.placer(),
+    };
+
+    let mut game = new_game(args, rules, true);
+
+    gameplay::place_ships(&mut game, HUMAN);
+
+    let fleet = match opponent_placer.place_fleet(game.rules()) {
+        Ok(fleet) => fleet,
+        Err(error) => {
+            eprintln!("ERROR: the computer can't place its fleet: {error}");
+            process::exit(1);
+        }
+    };
+    let mines = terrain::random_mines(game.rules(), &fleet, &mut Random::new());
+    if let Err(error) = game.place_fleet_and_mines(COMPUTER, &fleet, &mines) {
+        panic!("Opponent placed an invalid fleet: {error}");
+    }
+
+    play(args, game, Some(opponent_ai), profile);
+}
+
+/// Two humans taking turns at the same terminal.
+fn hotseat(args: &Args, rules: Rules) {
+    let mut game = new_game(args, rules, false);
+
+    for side in [Side::First, Side::Second] {
+        pass_to(side);
+        gameplay::place_ships(&mut game, side);
+        input::wait_for_enter("Press Enter to hide your ships.");
+    }
+
+    play(args, game, None, None);
+}
+
+fn resume(args: &Args, path: &str) {
+    let saved = match save::load(path) {
+        Ok(saved) if saved.game.phase() == Phase::Placement => {
+            eprintln!("ERROR: {path} was saved before both fleets were placed.");
+            process::exit(1);
+        }
+        Ok(saved) => saved,
+        Err(error) => {
+            eprintln!("ERROR: can't resume {path}: {error}");
+            process::exit(1);
+        }
+    };
+
+    continue_game(args, saved);
+}
+
+/// Finds a game that was interrupted before it ended, and asks whether
+/// to continue it.
+fn offer_autosave() -> Option<SavedGame> {
+    if !Path::new(save::AUTOSAVE_PATH).exists() {
+        return None;
+    }
+
+    match save::load(save::AUTOSAVE_PATH) {
+        Ok(saved) if saved.game.phase().shooting_side().is_some() => {
+            let kind = if saved.opponent.is_some() { "single player" } else { "hotseat" };
+            let message = format!("An unfinished {kind} game was found. Continue it?");
+            input::confirm(&message).then_some(saved)
+        }
+        Ok(_) => None,
+        Err(error) => {
+            eprintln!("WARNING: ignoring the autosave: {error}");
+            None
+        }
+    }
+}
+
+/// Continues a saved game, against the computer if it was saved with one.
+fn continue_game(args: &Args, saved: SavedGame) {
+    let mut game = saved.game;
+    set_up(args, &mut game, saved.opponent.is_some());
+    let profile = saved.opponent.as_ref().and_then(|_| load_profile(args, game.rules()));
+
+    play(args, game, saved.opponent, profile);
+}
+
+/// Plays the shooting out. With an `opponent` the computer shoots for the
+/// second side, without one two humans share the terminal.
+fn play(args: &Args, mut game: Game, mut opponent: Option<MonteCarloShooter>, mut profile: Option<Profile>) {
+    let mut notice = None;
+    let mut seated = None;
+
     loop {
-        clear_screen();
-        player.print();
-        let shot = input::read_safe::<Shot>("Input a shot 'y:x'");
-
-        match player.shoot(&mut opponent, shot.x, shot.y) {
-            Ok(victory) => {
-                match victory {
-                    Victory::Win => {
-                        clear_screen();
-                        println!("You won!");
-                        break;
-                    }
-                    Victory::NotWin => {
+        let side = match game.phase() {
+            Phase::Shooting(side) => side,
+            Phase::Placement => unreachable!("both fleets are placed"),
+            Phase::Finished(_) => break,
+        };
+
+        if let (Some(opponent_ai), COMPUTER) = (&mut opponent, side) {
+            let arsenal = game.arsenal(COMPUTER);
+            let special = if game.pending_shots().is_empty() && !arsenal.is_empty() {
+                opponent_ai.choose_special(game.player(COMPUTER).checked_field(), arsenal)
+            } else {
+                None
+            };
+            if let Some(special) = special {
+                match game.handle(Command::UseSpecial(COMPUTER, special)) {
+                    Ok(events) => {
+                        let report = gameplay::special_report(COMPUTER, special, &events);
+                        notice = Some(join_notices(notice, report));
                     }
+                    Err(error) => panic!("Opponent used {special} in an invalid place: {error}"),
                 }
-            }
-            Err(message) => {
-                eprintln!("{message}");
+                notice = autosave(&game, opponent.as_ref()).or(notice);
                 continue;
             }
+
+            let shots_left = game.volley_size(COMPUTER) - game.pending_shots().len();
+            let shots = opponent_ai.choose_volley(game.player(COMPUTER).checked_field(), shots_left);
+            for &shot in &shots {
+                if let Err(error) = game.handle(Command::Shoot(COMPUTER, shot)) {
+                    panic!("Opponent shot in an invalid place: {error}");
+                }
+            }
+            if let Some(report) = gameplay::shot_report(&game, COMPUTER, &shots) {
+                notice = Some(join_notices(notice, report));
+            }
+            notice = autosave(&game, opponent.as_ref()).or(notice);
+            continue;
+        }
+
+        let hotseat = opponent.is_none();
+        if hotseat && seated != Some(side) {
+            pass_to(side);
+            seated = Some(side);
         }
 
-        match opponent.shoot(&mut player, opponent_shot_x, opponent_shot_y) {
-            Ok(Victory::Win) => { 
-                clear_screen();
-                println!("You lost :(");
-                break;
+        match gameplay::take_turn(&mut game, side, notice.take()) {
+            Action::Shoot(shots) => {
+                notice = autosave(&game, opponent.as_ref());
+                let report = gameplay::shot_report(&game, side, &shots);
+                // A hit that earns another turn keeps the player seated.
+                let turn_over = game.phase() != Phase::Shooting(side);
+                if hotseat && turn_over {
+                    pretty_output::clear_screen();
+                    game.player(side).print();
+                    if let Some(report) = report {
+                        println!("{report}");
+                    }
+                    input::wait_for_enter("Press Enter to end your turn.");
+                } else if let Some(report) = report {
+                    notice = Some(join_notices(notice, report));
+                }
+            }
+            Action::Special { report, .. } => {
+                notice = autosave(&game, opponent.as_ref());
+                let turn_over = game.phase() != Phase::Shooting(side);
+                if hotseat && turn_over {
+                    pretty_output::clear_screen();
+                    game.player(side).print();
+                    println!("{report}");
+                    input::wait_for_enter("Press Enter to end your turn.");
+                } else {
+                    notice = Some(join_notices(notice, report));
+                }
+            }
+            Action::Save(path) => notice = Some(match save::save(&path, &game, opponent.as_ref()) {
+                Ok(()) => format!("Saved the game to {path}."),
+                Err(error) => format!("WARNING: {error}"),
+            }),
+            Action::Load(path) => notice = Some(match save::load(&path) {
+                Ok(saved) if saved.game.phase() == Phase::Placement => {
+                    format!("WARNING: {path} was saved before both fleets were placed.")
+                }
+                Ok(saved) => {
+                    game = saved.game;
+                    opponent = saved.opponent;
+                    set_up(args, &mut game, opponent.is_some());
+                    seated = game.phase().shooting_side();
+                    format!("Loaded the game from {path}.")
+                }
+                Err(error) => format!("WARNING: {error}"),
+            }),
+            Action::Undo => notice = match take_back(&mut game) {
+                Ok(()) => autosave(&game, opponent.as_ref()),
+                Err(error) => Some(format!("WARNING: {error}")),
+            },
+            Action::Redo => notice = match redo_turn(&mut game) {
+                Ok(()) => autosave(&game, opponent.as_ref()),
+                Err(error) => Some(format!("WARNING: {error}")),
             },
-            Ok(Victory::NotWin) => { }
-            Err(message) => {
-                panic!("Opponent shot in an invalid place: {message}");
+        }
+    }
+
+    // The game is over, there's nothing left to recover.
+    let _ = fs::remove_file(save::AUTOSAVE_PATH);
+    export(args, &game, opponent.is_some());
+
+    match opponent {
+        Some(_) => {
+            if let Some(profile) = &mut profile {
+                // Only the shots a human aimed say where they expect ships.
+                let shots: Vec<Shot> = game.history().iter()
+                    .filter_map(|(side, attack)| match attack {
+                        Attack::Shot(shot) if *side == HUMAN => Some(*shot),
+                        _ => None,
+                    })
+                    .collect();
+                profile.record_game(game.player(HUMAN).player_field(), &shots);
+                if let Err(error) = profile.save() {
+                    eprintln!("WARNING: {error}");
+                }
             }
+
+            gameplay::print_result(&game, HUMAN);
         }
-        opponent_shot_y += 1;
-        if opponent_shot_y >= FIELD_SIZE {
-            opponent_shot_x += 1;
-            opponent_shot_y = 0;
+        None => if let Phase::Finished(winner) = game.phase() {
+            pretty_output::clear_screen();
+            println!("{winner} won!");
+            gameplay::print_final_fields(&game, winner);
+        },
+    }
+}
+
+/// Takes back the human's last shot, whole volley or special attack, and
+/// the computer's answers to it.
+fn take_back(game: &mut Game) -> Result<(), GameError> {
+    loop {
+        // Never any further than the first shot.
+        if !game.last_command().is_some_and(|command| command.is_attack()) {
+            return Err(GameError::NothingToUndo);
+        }
+        if game.undo()?.side() == HUMAN && game.pending_shots().is_empty() {
+            return Ok(());
         }
     }
+}
+
+/// Fires the human's shot, volley or special attack taken back again, and
+/// the computer's answers.
+fn redo_turn(game: &mut Game) -> Result<(), GameError> {
+    game.redo()?;
+    while !game.pending_shots().is_empty()
+        || game.next_redo().is_some_and(|command| command.is_attack() && command.side() == COMPUTER)
+    {
+        game.redo()?;
+    }
+    Ok(())
+}
+
+/// `notice` with `line` added below it.
+fn join_notices(notice: Option<String>, line: String) -> String {
+    match notice {
+        Some(notice) => format!("{notice}\n{line}"),
+        None => line,
+    }
+}
+
+/// Writes the game down in the notation to the `--export` file, if any.
+fn export(args: &Args, game: &Game, against_computer: bool) {
+    let Some(path) = args.value("--export") else {
+        return;
+    };
+
+    let players = if against_computer {
+        [args.value("--name").unwrap_or("Human"), "Computer"]
+    } else {
+        ["Player 1", "Player 2"]
+    };
+    if let Err(error) = fs::write(path, notation::export(game, players)) {
+        eprintln!("WARNING: can't export the game to {path}: {error}");
+    }
+}
+
+/// Keeps the latest state on disk after every shot. Returns a warning if
+/// that didn't work.
+fn autosave(game: &Game, opponent: Option<&MonteCarloShooter>) -> Option<String> {
+    if matches!(game.phase(), Phase::Finished(_)) {
+        return None;
+    }
+
+    save::save(save::AUTOSAVE_PATH, game, opponent)
+        .err()
+        .map(|error| format!("WARNING: autosave failed: {error}"))
+}
 
-    println!("Final game state: ");
-    println!("Your field: ");
-    player.print();
-    println!("Enemy field: ");
-    opponent.print();
+/// Hides the field until the right player sits at the keyboard.
+fn pass_to(side: Side) {
+    pretty_output::clear_screen();
+    input::wait_for_enter(&format!("{side}, it's your turn. Press Enter when the other player isn't looking."));
 }
@@ -0,0 +1,147 @@
+use std::{process, str::FromStr, fmt::Display, thread, time::Duration};
+
+use battleships::{
+    battleships::{
+        game::{Game, Side, Phase},
+        field::CheckedCell,
+        replay::Replay,
+        shot::Shot,
+        weapons::{Attack, Special},
+    },
+    utilities::{args::Args, input, pretty_output},
+};
+
+const DEFAULT_SPEED_MS: u64 = 500;
+
+/// What can be typed while watching a replay.
+enum ReplayCommand {
+    Next,
+    Previous,
+    /// Go to the position after this many moves.
+    Jump(usize),
+    /// Play the rest of the game on its own.
+    Play,
+    Quit,
+}
+
+impl FromStr for ReplayCommand {
+    type Err = ParseReplayCommandError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" | "n" | "next" => Ok(ReplayCommand::Next),
+            "p" | "prev" | "previous" => Ok(ReplayCommand::Previous),
+            "a" | "play" => Ok(ReplayCommand::Play),
+            "q" | "quit" => Ok(ReplayCommand::Quit),
+            _ => {
+                let number = s.strip_prefix('g').unwrap_or(s).trim();
+                number.parse().map(ReplayCommand::Jump).map_err(|_| ParseReplayCommandError::InvalidInput)
+            }
+        }
+    }
+}
+
+enum ParseReplayCommandError {
+    InvalidInput,
+}
+
+impl Display for ParseReplayCommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseReplayCommandError::InvalidInput => {
+                write!(f, "use Enter/'n' for next, 'p' for previous, 'g <move>' to jump, 'a' to play, 'q' to quit")
+            }
+        }
+    }
+}
+
+fn main() {
+    let args = Args::from_env();
+    let Some(path) = args.first() else {
+        eprintln!("Usage: replay <file> [--speed <milliseconds per move>]");
+        process::exit(1);
+    };
+    let speed = Duration::from_millis(args.parsed("--speed").unwrap_or(DEFAULT_SPEED_MS));
+
+    let replay = match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(error) => {
+            eprintln!("ERROR: {error}");
+            process::exit(1);
+        }
+    };
+
+    let total = replay.moves().len();
+    let mut position = 0;
+
+    loop {
+        let game = replay.game_at(position);
+        show(&replay, &game, position);
+
+        let command = input::read_safe::<ReplayCommand>("[Enter] next, [p] previous, [g <move>] jump, [a] play, [q] quit");
+        match command {
+            ReplayCommand::Next => position = (position + 1).min(total),
+            ReplayCommand::Previous => position = position.saturating_sub(1),
+            ReplayCommand::Jump(target) => position = target.min(total),
+            ReplayCommand::Play => {
+                // Building every position from scratch is plenty fast
+                // for a game with a couple hundred moves at most.
+                while position < total {
+                    position += 1;
+                    show(&replay, &replay.game_at(position), position);
+                    thread::sleep(speed);
+                }
+            }
+            ReplayCommand::Quit => break,
+        }
+    }
+}
+
+fn show(replay: &Replay, game: &Game, position: usize) {
+    pretty_output::clear_screen();
+
+    let total = replay.moves().len();
+    match position.checked_sub(1).map(|index| &replay.moves()[index]) {
+        Some(step) => {
+            let seconds = step.elapsed.as_secs();
+            let outcome = |shot: &Shot| match game.player(step.side).checked_field().at(shot.x, shot.y) {
+                CheckedCell::Hit => "hit",
+                CheckedCell::Kill => "sunk",
+                _ => "miss",
+            };
+            let at = format!("Move {position}/{total} at {}:{:02}", seconds / 60, seconds % 60);
+            match step.attacks.as_slice() {
+                [Attack::Special(Special::Sonar(_))] => {
+                    // The ping of this move is the latest one.
+                    match game.player(step.side).checked_field().sonar_readings().last() {
+                        Some(reading) => println!("{at}: {}'s sonar found {reading}", step.side),
+                        None => println!("{at}: {} used sonar", step.side),
+                    }
+                }
+                [Attack::Special(special)] => println!("{at}: {} used {special}", step.side),
+                attacks => {
+                    let shots: Vec<Shot> = attacks.iter()
+                        .filter_map(|attack| match attack {
+                            Attack::Shot(shot) => Some(*shot),
+                            Attack::Special(_) => None,
+                        })
+                        .collect();
+                    let shots: Vec<String> = match shots.as_slice() {
+                        [shot] => vec![format!("{shot}, {}", outcome(shot))],
+                        shots => shots.iter().map(|shot| format!("{shot} {}", outcome(shot))).collect(),
+                    };
+                    println!("{at}: {} fired at {}", step.side, shots.join(", "));
+                }
+            }
+        }
+        None => println!("Move 0/{total}: both fleets are placed"),
+    }
+    if let Phase::Finished(winner) = game.phase() {
+        println!("{winner} won!");
+    }
+
+    for side in [Side::First, Side::Second] {
+        println!("{side}:");
+        game.player(side).print();
+    }
+}
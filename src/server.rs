@@ -1,61 +1,88 @@
-mod battleships;
-mod utilities;
-mod gameplay;
-
-use std::net::{TcpListener, TcpStream};
+use std::{net::TcpListener, process};
 
 use battleships::{
-    player::Player,
-    game_constants::FIELD_SIZE, 
+    battleships::{
+        feasibility::{self, FeasibilityError},
+        game::Side,
+        game_constants::MAX_FIELD_SIZE,
+        rules::{Rules, FieldSize, Salvo, TouchingPolicy, TurnOrder},
+        shape,
+        terrain,
+        weapons::Arsenal,
+    },
+    gameplay,
+    utilities::{args::Args, random::Random},
 };
 
-use utilities::unsafe_net::{self, MAGIC_BYTE_PLACE, MAGIC_BYTE_SHOOT, MAGIC_BYTE_GET_SHOT};
-
 const ADDRESS: &str = "127.0.0.1:6969";
 
 fn main() {
-    let listener = TcpListener::bind(ADDRESS).unwrap();
-    let mut player = Player::new(FIELD_SIZE);
-
-    for stream in listener.incoming() {
-        let stream = stream.unwrap();
-        start_game(stream, &mut player);
+    let args = Args::from_env();
+    let mut rules = Rules {
+        salvo: args.parsed::<Salvo>("--salvo").unwrap_or(Salvo::Off),
+        turns: args.parsed::<TurnOrder>("--turns").unwrap_or(TurnOrder::Alternate),
+        arsenal: args.parsed::<Arsenal>("--arsenal").unwrap_or_default(),
+        mines: args.parsed::<usize>("--mines").unwrap_or(0),
+        ..Rules::default()
+    };
+    if let Some(size) = args.parsed::<FieldSize>("--size") {
+        if size.width > MAX_FIELD_SIZE || size.height > MAX_FIELD_SIZE {
+            eprintln!("ERROR: --size can be at most {MAX_FIELD_SIZE} each way");
+            process::exit(1);
+        }
+        rules.size = size;
     }
-}
-
-fn start_game(mut opponent_stream: TcpStream, player: &mut Player) {
-    loop {
-        let mut opponent_message_buf = [0u8; 3];
-        unsafe_net::read_blocking(&mut opponent_stream, &mut opponent_message_buf);
-
-        println!("Read the stream: {}", opponent_message_buf[0]);
-        if opponent_message_buf[0] == MAGIC_BYTE_PLACE {
-            gameplay::place_ships(player);
-        } else if opponent_message_buf[0] == MAGIC_BYTE_SHOOT {
-            shoot_loop(&mut opponent_stream, player);
-        } 
+    if let Some(touching) = args.parsed::<TouchingPolicy>("--touching") {
+        rules.touching = touching;
     }
-}
-
-fn shoot_loop(stream: &mut TcpStream, player: &mut Player) {
-    loop {
-        shoot_client(stream, player);
-        take_shot(stream, player);
+    if matches!(rules.salvo, Salvo::Fixed(shots) if shots > rules.size.cells()) {
+        eprintln!("ERROR: --salvo can't have more shots than the {} cells of the field", rules.size.cells());
+        process::exit(1);
     }
-}
-
-fn shoot_client(stream: &mut TcpStream, player: &mut Player) {
-    let shot = gameplay::read_shot(player);
-    unsafe_net::send(stream, &[MAGIC_BYTE_GET_SHOT, shot.x as u8, shot.y as u8]);
-}
-
-fn take_shot(stream: &mut TcpStream, player: &mut Player) {
-    let mut shot_buf = [0u8; 3];
-    unsafe_net::read_blocking(stream, &mut shot_buf);
+    if let Some(fleet) = args.value("--fleet") {
+        match shape::parse_fleet(fleet) {
+            Ok(fleet) => rules.fleet = fleet,
+            Err(error) => {
+                eprintln!("ERROR: can't understand --fleet '{fleet}': {error}");
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(islands) = args.parsed::<usize>("--islands") {
+        match terrain::generate_islands(&rules, islands, &mut Random::new()) {
+            Ok(islands) => rules.islands = islands,
+            Err(error) => {
+                eprintln!("ERROR: can't put {islands} island cells on the field: {error}");
+                process::exit(1);
+            }
+        }
+    }
+    match feasibility::solve(&rules) {
+        Ok(_) => {}
+        Err(FeasibilityError::Undecided) => eprintln!("WARNING: {}", FeasibilityError::Undecided),
+        Err(error) => {
+            eprintln!("ERROR: can't play on a {}x{} field: {error}", rules.size.width, rules.size.height);
+            process::exit(1);
+        }
+    }
+    let listener = match TcpListener::bind(ADDRESS) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("ERROR: can't listen on {ADDRESS}: {error}");
+            process::exit(1);
+        }
+    };
 
-    if shot_buf[0] == MAGIC_BYTE_GET_SHOT {
-        // TODO: Also send the result of this, with errors, etc.
-        player.take_damage(shot_buf[1].into(), shot_buf[2].into());
-        player.print();
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                eprintln!("ERROR: can't accept the connection: {error}");
+                continue;
+            }
+        };
+        if let Err(error) = gameplay::play_online(&mut stream, Side::First, Some(rules.clone())) {
+            eprintln!("ERROR: {error}");
+        }
     }
 }
@@ -0,0 +1,50 @@
+use std::{env, str::FromStr, fmt::Display, process};
+
+/// Command line arguments in the `--name value` or `--name=value` form.
+pub struct Args {
+    args: Vec<String>,
+}
+
+impl Args {
+    pub fn from_env() -> Args {
+        Args { args: env::args().skip(1).collect() }
+    }
+
+    /// The first argument, unless it is an option.
+    pub fn first(&self) -> Option<&str> {
+        self.args.first().map(String::as_str).filter(|arg| !arg.starts_with("--"))
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.args.iter().any(|arg| arg == name)
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        let prefix = format!("{name}=");
+
+        self.args.iter().enumerate().find_map(|(i, arg)| {
+            if arg == name {
+                self.args.get(i + 1).map(String::as_str)
+            } else {
+                arg.strip_prefix(&prefix)
+            }
+        })
+    }
+
+    /// Parses the value of `name`, quitting with an error message if it
+    /// is there but can't be understood.
+    pub fn parsed<T>(&self, name: &str) -> Option<T>
+    where T: FromStr,
+          T::Err: Display
+    {
+        let value = self.value(name)?;
+
+        match value.parse::<T>() {
+            Ok(value) => Some(value),
+            Err(error) => {
+                eprintln!("ERROR: can't understand {name} '{value}': {error}");
+                process::exit(1);
+            }
+        }
+    }
+}
@@ -1,17 +1,31 @@
-use crate::battleships::game_constants::{FIRST_LETTER, LAST_LETTER};
+/// The label of a row: `A` to `Z`, then `AA`, `AB`, ... like spreadsheet
+/// columns, so that fields can have any number of rows.
+pub fn usize_to_label(row: usize) -> String {
+    let mut label = Vec::new();
+    let mut rest = row + 1;
+    while rest > 0 {
+        rest -= 1;
+        label.push(b'A' + (rest % 26) as u8);
+        rest /= 26;
+    }
+    label.reverse();
 
-pub fn coordinate_to_usize(character: char) -> Option<usize> {
-    let character = character as usize;
-    let first = FIRST_LETTER as usize;
-    let last = LAST_LETTER as usize;
+    String::from_utf8(label).expect("labels are ASCII")
+}
 
-    if character < first || character > last {
-        None
-    } else {
-        Some(character - first)
+/// The row a label like `C` or `AB` stands for, in either case. Labels go
+/// on after `Z` like spreadsheet columns do. `None` if it isn't a label or
+/// is too long to be a row of any field.
+pub fn label_to_usize(label: &str) -> Option<usize> {
+    if label.is_empty() {
+        return None;
     }
-}
 
-pub fn usize_to_coordinate(coordinate: usize) -> char {
-    (FIRST_LETTER as u8 + coordinate as u8) as char
+    label.chars().try_fold(0usize, |row, letter| {
+        if !letter.is_ascii_alphabetic() {
+            return None;
+        }
+        let letter = (letter.to_ascii_uppercase() as u8 - b'A') as usize;
+        row.checked_mul(26)?.checked_add(letter + 1)
+    }).map(|row| row - 1)
 }
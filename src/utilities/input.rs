@@ -1,7 +1,9 @@
-use std::{str::FromStr, io, fmt, fmt::Display};
+use std::{str::FromStr, io, fmt, fmt::Display, process};
 
 pub enum ReadError<T: FromStr> {
     IOError,
+    /// There's nothing left to read, like when the input was piped in.
+    EndOfInput,
     ParsingError(T::Err),
 }
 
@@ -12,6 +14,7 @@ where T: FromStr,
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::IOError => write!(f, "Unknown IO error."),
+            Self::EndOfInput => write!(f, "the input ended"),
             Self::ParsingError(error) => write!(f, "{error}"),
         }
     }
@@ -24,6 +27,7 @@ where T: FromStr,
     loop {
         match read(message) {
             Ok(value) => { return value },
+            Err(ReadError::EndOfInput) => end_of_input(),
             Err(error) => { println!("ERROR: {error}. Please try again.") },
         };
     }
@@ -43,18 +47,49 @@ where T: FromStr,
                     Err(error) => { println!("ERROR: {error}. Please try again.") },
                 }
             },
+            Err(ReadError::EndOfInput) => end_of_input(),
             Err(error) => { println!("ERROR: {error}. Please try again.") },
         }
     }
 }
 
+/// Asks a yes or no question until it gets one of them. No answer at all
+/// counts as a no.
+pub fn confirm(message: &str) -> bool {
+    loop {
+        match read::<String>(&format!("{message} [y/n]")) {
+            Ok(answer) if matches!(answer.as_str(), "y" | "yes") => return true,
+            Ok(answer) if matches!(answer.as_str(), "n" | "no") => return false,
+            Ok(_) => println!("ERROR: please answer 'y' or 'n'."),
+            Err(ReadError::EndOfInput) => return false,
+            Err(error) => println!("ERROR: {error}. Please try again."),
+        }
+    }
+}
+
+/// Stops the program once nothing is left to ask the player, since every
+/// question would be asked again forever.
+fn end_of_input() -> ! {
+    eprintln!("ERROR: the input ended");
+    process::exit(1);
+}
+
+pub fn wait_for_enter(message: &str) {
+    println!("{message}");
+
+    let mut buf = String::new();
+    let _ = io::stdin().read_line(&mut buf);
+}
+
 pub fn read<T: FromStr>(message: &str) -> Result<T, ReadError<T>> {
     println!("{message}");
 
     let mut buf = String::new();
 
-    if io::stdin().read_line(&mut buf).is_err() {
-        return Err(ReadError::IOError);
+    match io::stdin().read_line(&mut buf) {
+        Ok(0) => return Err(ReadError::EndOfInput),
+        Ok(_) => {}
+        Err(_) => return Err(ReadError::IOError),
     }
 
     match buf.trim().parse::<T>() {
@@ -2,3 +2,5 @@ pub mod input;
 pub mod conversions;
 pub mod unsafe_net;
 pub mod pretty_output;
+pub mod random;
+pub mod args;
@@ -1,20 +1,380 @@
-use std::{net::TcpStream, io::{Read, Write}};
+use std::{fmt::Display, io::{self, Read, Write}, net::TcpStream};
 
-pub const MAGIC_BYTE_PLACE: u8 = 1;
-pub const MAGIC_BYTE_SHOOT: u8 = 2;
-pub const MAGIC_BYTE_GET_SHOT: u8 = 2;
+use crate::battleships::{
+    game::Answer,
+    player::Outcome,
+    shape::Shape,
+    ship::{Ship, Rotation},
+    shot::Shot,
+    game_constants::MAX_FIELD_SIZE,
+    rules::{Rules, FieldSize, Salvo, TouchingPolicy, TurnOrder},
+    weapons::{Arsenal, Attack, Special, Heading},
+};
 
-pub fn send(stream: &mut TcpStream, bytes: &[u8]) {
-    stream.write(bytes).unwrap();
+pub const MAGIC_BYTE_FLEET: u8 = 1;
+pub const MAGIC_BYTE_VOLLEY: u8 = 2;
+pub const MAGIC_BYTE_RULES: u8 = 3;
+pub const MAGIC_BYTE_SPECIAL: u8 = 4;
+pub const MAGIC_BYTE_READY: u8 = 5;
+pub const MAGIC_BYTE_ANSWER: u8 = 6;
+pub const MAGIC_BYTE_DAMAGE: u8 = 7;
+
+pub fn send(stream: &mut TcpStream, bytes: &[u8]) -> Result<(), NetError> {
+    stream.write_all(bytes).map_err(NetError::IOError)
+}
+
+pub fn read_blocking(stream: &mut TcpStream, buf: &mut [u8]) -> Result<(), NetError> {
+    stream.read_exact(buf).map_err(NetError::IOError)
+}
+
+/// Reads a single byte.
+fn read_byte(stream: &mut TcpStream) -> Result<u8, NetError> {
+    let mut byte = [0u8; 1];
+    read_blocking(stream, &mut byte)?;
+    Ok(byte[0])
+}
+
+/// A shape as `[kind, length]`, where the kind is `0` for a straight
+/// ship of that length and `1` to `4` for L, T, O and Z.
+fn shape_bytes(shape: Shape) -> [u8; 2] {
+    match shape {
+        Shape::Line(length) => [0, length as u8],
+        Shape::L => [1, 4],
+        Shape::T => [2, 4],
+        Shape::Square => [3, 4],
+        Shape::Z => [4, 4],
+    }
+}
+
+fn read_shape(bytes: &[u8]) -> Result<Shape, NetError> {
+    match bytes[0] {
+        0 => Ok(Shape::Line(bytes[1].into())),
+        1 => Ok(Shape::L),
+        2 => Ok(Shape::T),
+        3 => Ok(Shape::Square),
+        4 => Ok(Shape::Z),
+        kind => Err(NetError::BadMessage(format!("an unknown ship shape {kind}"))),
+    }
+}
+
+/// A ship as `[kind, length, x, y, rotation]`, with the shape as in
+/// `shape_bytes`. The rotation is `0` to `3` as in `Rotation::ALL`, plus
+/// `4` if mirrored.
+fn ship_bytes(ship: &Ship) -> [u8; 5] {
+    let turns = Rotation::ALL.iter().position(|rotation| *rotation == ship.rotation).unwrap_or(0);
+    let rotation = turns as u8 + if ship.mirrored { 4 } else { 0 };
+    let [kind, length] = shape_bytes(ship.shape);
+    [kind, length, ship.x as u8, ship.y as u8, rotation]
+}
+
+fn read_ship(bytes: &[u8]) -> Result<Ship, NetError> {
+    if bytes[4] >= 8 {
+        return Err(NetError::BadMessage(format!("an unknown ship rotation {}", bytes[4])));
+    }
+    Ok(Ship {
+        shape: read_shape(&bytes[0..2])?,
+        x: bytes[2].into(),
+        y: bytes[3].into(),
+        rotation: Rotation::ALL[(bytes[4] % 4) as usize],
+        mirrored: bytes[4] >= 4,
+    })
+}
+
+/// Tells the opponent that the fleet is placed, as `[MAGIC_BYTE_READY]`.
+/// The fleet itself stays here until the game is over.
+pub fn send_ready(stream: &mut TcpStream) -> Result<(), NetError> {
+    send(stream, &[MAGIC_BYTE_READY])
+}
+
+/// Waits until the opponent has placed their fleet.
+pub fn read_ready(stream: &mut TcpStream) -> Result<(), NetError> {
+    match read_byte(stream)? {
+        MAGIC_BYTE_READY => Ok(()),
+        _ => Err(NetError::BadMessage("something else than that their fleet is ready".to_string())),
+    }
+}
+
+/// Shows the whole fleet and the hidden mines once the game is over, as
+/// `[MAGIC_BYTE_FLEET, count, ship..., mines, (x, y)...]` with the ships
+/// as in `ship_bytes`.
+pub fn send_fleet(stream: &mut TcpStream, ships: &[Ship], mines: &[Shot]) -> Result<(), NetError> {
+    let mut message = vec![MAGIC_BYTE_FLEET, ships.len() as u8];
+    for ship in ships {
+        message.extend(ship_bytes(ship));
+    }
+    message.push(mines.len() as u8);
+    for mine in mines {
+        message.extend([mine.x as u8, mine.y as u8]);
+    }
+    send(stream, &message)
+}
+
+pub fn read_fleet(stream: &mut TcpStream) -> Result<(Vec<Ship>, Vec<Shot>), NetError> {
+    let mut header = [0u8; 2];
+    read_blocking(stream, &mut header)?;
+    if header[0] != MAGIC_BYTE_FLEET {
+        return Err(NetError::BadMessage("something else than their fleet".to_string()));
+    }
+
+    let mut ships = vec![0u8; header[1] as usize * 5];
+    read_blocking(stream, &mut ships)?;
+    let ships = ships.chunks(5).map(read_ship).collect::<Result<_, _>>()?;
+
+    Ok((ships, read_cells(stream)?.into_iter().map(|(x, y)| Shot { x, y }).collect()))
+}
+
+/// An outcome as `0` for a miss, `1` for a hit, `2` for a sunk ship,
+/// which follows as in `ship_bytes`, and `3` for a mine.
+fn outcome_bytes(outcome: &Outcome) -> Vec<u8> {
+    match outcome {
+        Outcome::Miss => vec![0],
+        Outcome::Hit => vec![1],
+        Outcome::Sunk(ship) => [2].into_iter().chain(ship_bytes(ship)).collect(),
+        Outcome::Mine => vec![3],
+    }
+}
+
+fn read_outcome(stream: &mut TcpStream) -> Result<Outcome, NetError> {
+    match read_byte(stream)? {
+        0 => Ok(Outcome::Miss),
+        1 => Ok(Outcome::Hit),
+        2 => {
+            let mut ship = [0u8; 5];
+            read_blocking(stream, &mut ship)?;
+            Ok(Outcome::Sunk(read_ship(&ship)?))
+        }
+        3 => Ok(Outcome::Mine),
+        outcome => Err(NetError::BadMessage(format!("an unknown outcome {outcome}"))),
+    }
+}
+
+/// Tells the opponent what their attack did to the fleet here, as
+/// `[MAGIC_BYTE_ANSWER, sonar, count, (x, y, outcome)...]`. `sonar` is
+/// how many ship cells a sonar ping found, and the outcomes are as in
+/// `outcome_bytes`.
+pub fn send_answer(stream: &mut TcpStream, answer: &Answer) -> Result<(), NetError> {
+    let mut message = vec![MAGIC_BYTE_ANSWER, answer.ship_cells as u8, answer.cells.len() as u8];
+    for (cell, outcome) in &answer.cells {
+        message.extend([cell.x as u8, cell.y as u8]);
+        message.extend(outcome_bytes(outcome));
+    }
+    send(stream, &message)
+}
+
+pub fn read_answer(stream: &mut TcpStream) -> Result<Answer, NetError> {
+    let mut header = [0u8; 3];
+    read_blocking(stream, &mut header)?;
+    if header[0] != MAGIC_BYTE_ANSWER {
+        return Err(NetError::BadMessage("something else than what the attack did".to_string()));
+    }
+
+    let mut answer = Answer { ship_cells: header[1].into(), ..Answer::default() };
+    for _ in 0..header[2] {
+        let mut cell = [0u8; 2];
+        read_blocking(stream, &mut cell)?;
+        let outcome = read_outcome(stream)?;
+        answer.cells.push((Shot { x: cell[0].into(), y: cell[1].into() }, outcome));
+    }
+    Ok(answer)
+}
+
+/// Tells the opponent what the mines they answered with did to the fleet
+/// here, as `[MAGIC_BYTE_DAMAGE, count, damage...]`. Each mine is `0` if
+/// there was no ship left to damage, or `1, x, y, outcome` with the
+/// outcome as in `outcome_bytes`.
+pub fn send_damage(stream: &mut TcpStream, damage: &[Option<(Shot, Outcome)>]) -> Result<(), NetError> {
+    let mut message = vec![MAGIC_BYTE_DAMAGE, damage.len() as u8];
+    for mine in damage {
+        match mine {
+            None => message.push(0),
+            Some((cell, outcome)) => {
+                message.extend([1, cell.x as u8, cell.y as u8]);
+                message.extend(outcome_bytes(outcome));
+            }
+        }
+    }
+    send(stream, &message)
+}
+
+pub fn read_damage(stream: &mut TcpStream) -> Result<Vec<Option<(Shot, Outcome)>>, NetError> {
+    let mut header = [0u8; 2];
+    read_blocking(stream, &mut header)?;
+    if header[0] != MAGIC_BYTE_DAMAGE {
+        return Err(NetError::BadMessage("something else than what the mines did".to_string()));
+    }
+
+    let mut damage = Vec::new();
+    for _ in 0..header[1] {
+        damage.push(match read_byte(stream)? {
+            0 => None,
+            1 => {
+                let mut cell = [0u8; 2];
+                read_blocking(stream, &mut cell)?;
+                Some((Shot { x: cell[0].into(), y: cell[1].into() }, read_outcome(stream)?))
+            }
+            flag => return Err(NetError::BadMessage(format!("an unknown mine damage {flag}"))),
+        });
+    }
+    Ok(damage)
+}
+
+/// Reads a count followed by that many `(x, y)` pairs.
+fn read_cells(stream: &mut TcpStream) -> Result<Vec<(usize, usize)>, NetError> {
+    let count = read_byte(stream)?;
+    let mut cells = vec![0u8; count as usize * 2];
+    read_blocking(stream, &mut cells)?;
+
+    Ok(cells.chunks(2).map(|cell| (cell[0].into(), cell[1].into())).collect())
+}
+
+/// Sends the shots of one turn as `[MAGIC_BYTE_VOLLEY, count, (x, y)...]`.
+pub fn send_volley(stream: &mut TcpStream, shots: &[Shot]) -> Result<(), NetError> {
+    let mut message = vec![MAGIC_BYTE_VOLLEY, shots.len() as u8];
+    for shot in shots {
+        message.extend([shot.x as u8, shot.y as u8]);
+    }
+    send(stream, &message)
+}
+
+/// Sends a special attack as `[MAGIC_BYTE_SPECIAL, weapon, a, b]`. The
+/// weapon is `0` for a bomb and `2` for sonar, with `a` and `b` the `x`
+/// and `y` of the centre, or `1` for a torpedo, with the row and `0` to
+/// run east or `1` to run west.
+pub fn send_special(stream: &mut TcpStream, special: Special) -> Result<(), NetError> {
+    let (weapon, a, b) = match special {
+        Special::Bomb(centre) => (0, centre.x, centre.y),
+        Special::Torpedo { row, heading } => (1, row, if heading == Heading::East { 0 } else { 1 }),
+        Special::Sonar(centre) => (2, centre.x, centre.y),
+    };
+    send(stream, &[MAGIC_BYTE_SPECIAL, weapon, a as u8, b as u8])
+}
+
+/// Reads what the opponent fired on their turn: the shots of a volley, or
+/// a single special attack.
+pub fn read_attacks(stream: &mut TcpStream) -> Result<Vec<Attack>, NetError> {
+    let mut header = [0u8; 2];
+    read_blocking(stream, &mut header)?;
+
+    match header[0] {
+        MAGIC_BYTE_VOLLEY => {
+            let mut shots = vec![0u8; header[1] as usize * 2];
+            read_blocking(stream, &mut shots)?;
+
+            Ok(shots.chunks(2).map(|shot| Attack::Shot(Shot { x: shot[0].into(), y: shot[1].into() })).collect())
+        }
+        MAGIC_BYTE_SPECIAL => {
+            let mut target = [0u8; 2];
+            read_blocking(stream, &mut target)?;
+            let (a, b) = (target[0].into(), target[1].into());
+
+            let special = match header[1] {
+                0 => Special::Bomb(Shot { x: a, y: b }),
+                1 => {
+                    let heading = match b {
+                        0 => Heading::East,
+                        1 => Heading::West,
+                        heading => return Err(NetError::BadMessage(format!("an unknown torpedo heading {heading}"))),
+                    };
+                    Special::Torpedo { row: a, heading }
+                }
+                2 => Special::Sonar(Shot { x: a, y: b }),
+                weapon => return Err(NetError::BadMessage(format!("an unknown weapon {weapon}"))),
+            };
+            Ok(vec![Attack::Special(special)])
+        }
+        _ => Err(NetError::BadMessage("something else than their shots".to_string())),
+    }
+}
+
+/// Sends the rules that can be changed for online games as
+/// `[MAGIC_BYTE_RULES, width, height, touching, salvo kind, shots, turns,
+/// bombs, torpedoes, sonars, mines, islands, (x, y)..., ships,
+/// (kind, length)...]`, with the ships as in `shape_bytes`. Touching is
+/// `0` for no touch, `1` for corners only and `2` for anything. The salvo
+/// kind is `0` for off, `1` for ships afloat and `2` for a fixed count of
+/// shots, and turns are `0` to alternate and `1` when a hit shoots again.
+pub fn send_rules(stream: &mut TcpStream, rules: &Rules) -> Result<(), NetError> {
+    let (kind, shots) = match rules.salvo {
+        Salvo::Off => (0, 0),
+        Salvo::ShipsAfloat => (1, 0),
+        Salvo::Fixed(shots) => (2, shots as u8),
+    };
+    let turns = match rules.turns {
+        TurnOrder::Alternate => 0,
+        TurnOrder::HitShootsAgain => 1,
+    };
+    let Arsenal { bombs, torpedoes, sonars } = rules.arsenal;
+    let touching = match rules.touching {
+        TouchingPolicy::NoTouch => 0,
+        TouchingPolicy::CornersOnly => 1,
+        TouchingPolicy::Anything => 2,
+    };
+    let FieldSize { width, height } = rules.size;
+    let mut message = vec![MAGIC_BYTE_RULES, width as u8, height as u8, touching, kind, shots, turns];
+    message.extend([bombs as u8, torpedoes as u8, sonars as u8, rules.mines as u8, rules.islands.len() as u8]);
+    for &(x, y) in &rules.islands {
+        message.extend([x as u8, y as u8]);
+    }
+    message.push(rules.fleet.len() as u8);
+    for &shape in &rules.fleet {
+        message.extend(shape_bytes(shape));
+    }
+    send(stream, &message)
+}
+
+pub fn read_rules(stream: &mut TcpStream) -> Result<Rules, NetError> {
+    let mut rules = [0u8; 11];
+    read_blocking(stream, &mut rules)?;
+    if rules[0] != MAGIC_BYTE_RULES {
+        return Err(NetError::BadMessage("something else than the rules".to_string()));
+    }
+
+    let size = FieldSize { width: rules[1].into(), height: rules[2].into() };
+    if size.width == 0 || size.height == 0 || size.width > MAX_FIELD_SIZE || size.height > MAX_FIELD_SIZE {
+        return Err(NetError::BadMessage(format!("an unsupported field size {size}")));
+    }
+    let touching = match rules[3] {
+        0 => TouchingPolicy::NoTouch,
+        1 => TouchingPolicy::CornersOnly,
+        2 => TouchingPolicy::Anything,
+        policy => return Err(NetError::BadMessage(format!("an unknown touching policy {policy}"))),
+    };
+    let salvo = match rules[4] {
+        0 => Salvo::Off,
+        1 => Salvo::ShipsAfloat,
+        _ => Salvo::Fixed(rules[5].into()),
+    };
+    let turns = if rules[6] == 0 { TurnOrder::Alternate } else { TurnOrder::HitShootsAgain };
+    let arsenal = Arsenal { bombs: rules[7].into(), torpedoes: rules[8].into(), sonars: rules[9].into() };
+    let mines = rules[10].into();
+    let islands = read_cells(stream)?;
+
+    let count = read_byte(stream)?;
+    let mut fleet = vec![0u8; count as usize * 2];
+    read_blocking(stream, &mut fleet)?;
+    let fleet = fleet.chunks(2).map(read_shape).collect::<Result<_, _>>()?;
+
+    let rules = Rules { size, fleet, touching, salvo, turns, arsenal, islands, mines };
+    if let Some(&(x, y)) = rules.islands.iter().find(|&&(x, y)| x >= rules.size.width || y >= rules.size.height) {
+        return Err(NetError::BadMessage(format!("an island off the field at {}", Shot { x, y })));
+    }
+    Ok(rules)
+}
+
+/// Why a game over the network can't go on.
+#[derive(Debug)]
+pub enum NetError {
+    IOError(io::Error),
+    /// The opponent sent something that makes no sense, said like "an
+    /// unknown ship shape 7".
+    BadMessage(String),
 }
 
-pub fn read_blocking(stream: &mut TcpStream, buf: &mut [u8]) {
-    loop {
-        let count = stream.read(buf).unwrap();
-        if count == 0 {
-            continue;
-        } else {
-            break;
+impl Display for NetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetError::IOError(inner) => write!(f, "lost the connection to the opponent: {inner}"),
+            NetError::BadMessage(what) => write!(f, "the opponent sent {what}"),
         }
     }
 }